@@ -0,0 +1,64 @@
+use std::mem;
+
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // Gives some thickness to flat boxes so that rays can still hit them.
+    pub fn padded(&self) -> Aabb {
+        const MIN_EXTENT: f64 = 1e-4;
+        let d = self.max - self.min;
+        let pad = Vec3::new(
+            if d.x() < MIN_EXTENT { MIN_EXTENT } else { 0.0 },
+            if d.y() < MIN_EXTENT { MIN_EXTENT } else { 0.0 },
+            if d.z() < MIN_EXTENT { MIN_EXTENT } else { 0.0 },
+        );
+        Aabb {
+            min: self.min - 0.5 * pad,
+            max: self.max + 0.5 * pad,
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
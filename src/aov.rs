@@ -0,0 +1,130 @@
+use std::f64;
+
+use crate::hitable::HitRecord;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Extra passes rendered along with the image, from the first hit of the camera rays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    ObjectId,
+    MaterialId,
+    Position,
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Aov> {
+        match name {
+            "depth" => Some(Aov::Depth),
+            "normal" => Some(Aov::Normal),
+            "albedo" => Some(Aov::Albedo),
+            "object_id" => Some(Aov::ObjectId),
+            "material_id" => Some(Aov::MaterialId),
+            "position" => Some(Aov::Position),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Position => "position",
+        }
+    }
+
+    // Names of the channels of the pass in OpenEXR images, one per used component.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["normal.X", "normal.Y", "normal.Z"],
+            Aov::Albedo => &["albedo.R", "albedo.G", "albedo.B"],
+            Aov::ObjectId => &["object_id"],
+            Aov::MaterialId => &["material_id"],
+            Aov::Position => &["position.X", "position.Y", "position.Z"],
+        }
+    }
+
+    // Unprocessed value of the pass, `hit_record` is None when the ray left the scene.
+    pub fn value(self, ray: &Ray, hit_record: Option<&HitRecord>) -> Vec3 {
+        let hit_record = match hit_record {
+            Some(hit_record) => hit_record,
+            None if self == Aov::Depth => return Vec3::one() * f64::INFINITY,
+            None => return Vec3::zero(),
+        };
+        match self {
+            Aov::Depth => Vec3::one() * hit_record.t * ray.direction.length(),
+            Aov::Normal => hit_record.normal.normalized(),
+            Aov::Albedo => hit_record.material.albedo(hit_record),
+            Aov::ObjectId => Vec3::one() * hit_record.ids.object as f64,
+            Aov::MaterialId => Vec3::one() * hit_record.ids.material as f64,
+            Aov::Position => hit_record.p,
+        }
+    }
+
+    // Maps the values of the pass to displayable colors in [0, 1]. Depth and position
+    // are normalized to the range covered by the image, identifiers get random colors.
+    pub fn display(self, values: &[Vec3]) -> Vec<Vec3> {
+        match self {
+            Aov::Depth => {
+                let max = values
+                    .iter()
+                    .map(|v| v.x())
+                    .filter(|d| d.is_finite())
+                    .fold(0.0, f64::max);
+                values
+                    .iter()
+                    .map(|v| Vec3::one() * (v.x() / max).min(1.0))
+                    .collect()
+            }
+            Aov::Normal => values
+                .iter()
+                .map(|n| {
+                    if n.squared_length() > 0.0 {
+                        0.5 * (*n + 1.0)
+                    } else {
+                        Vec3::zero()
+                    }
+                })
+                .collect(),
+            Aov::Albedo => values.to_vec(),
+            Aov::ObjectId | Aov::MaterialId => values.iter().map(|v| id_color(v.x())).collect(),
+            Aov::Position => {
+                // Rays leaving the scene are at the origin and left out.
+                let hits = values.iter().filter(|v| v.squared_length() > 0.0);
+                let min = hits.clone().fold(Vec3::one() * f64::MAX, |a, v| a.min(v));
+                let max = hits.fold(Vec3::one() * f64::MIN, |a, v| a.max(v));
+                let size = (max - min).max(&(Vec3::one() * f64::EPSILON));
+                values
+                    .iter()
+                    .map(|v| {
+                        if v.squared_length() == 0.0 {
+                            return Vec3::zero();
+                        }
+                        let v = *v - min;
+                        Vec3::new(v.x() / size.x(), v.y() / size.y(), v.z() / size.z())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// Well spread color of an identifier, black for 0.
+fn id_color(id: f64) -> Vec3 {
+    if id <= 0.0 {
+        return Vec3::zero();
+    }
+    let mut hash = (id as u32).wrapping_mul(0x9E37_79B9);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xFF) as f64 / 255.0;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use crate::environment::EnvironmentMap;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+#[derive(Clone)]
+pub enum Background {
+    Solid(Vec3),
+    Gradient { bottom: Vec3, top: Vec3 },
+    Environment(Arc<EnvironmentMap>),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Vec3 {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (ray.direction.normalized().y() + 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Background::Environment(map) => map.color(&ray.direction),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Vec3::one(),
+            top: Vec3::new(0.5, 0.7, 1.0),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::cmp::Ordering;
+
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable, HitableArc};
+use crate::ray::Ray;
+
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+enum BvhNode {
+    Leaf {
+        bbox: Aabb,
+        objects: Vec<HitableArc>,
+    },
+    Node {
+        bbox: Aabb,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+struct Primitive {
+    bbox: Aabb,
+    centroid: f64,
+    object: HitableArc,
+}
+
+pub struct Bvh {
+    root: Option<BvhNode>,
+    unbounded: Vec<HitableArc>,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<HitableArc>) -> Bvh {
+        let mut primitives = Vec::new();
+        let mut unbounded = Vec::new();
+        for object in objects {
+            match object.bounding_box() {
+                Some(bbox) => primitives.push(Primitive {
+                    bbox,
+                    centroid: 0.0,
+                    object,
+                }),
+                None => unbounded.push(object),
+            }
+        }
+        let root = if primitives.is_empty() {
+            None
+        } else {
+            Some(Self::build(primitives))
+        };
+        Bvh { root, unbounded }
+    }
+
+    fn bounds(primitives: &[Primitive]) -> Aabb {
+        primitives
+            .iter()
+            .skip(1)
+            .fold(primitives[0].bbox, |bbox, p| bbox.surrounding(&p.bbox))
+    }
+
+    fn leaf(bbox: Aabb, primitives: Vec<Primitive>) -> BvhNode {
+        BvhNode::Leaf {
+            bbox,
+            objects: primitives.into_iter().map(|p| p.object).collect(),
+        }
+    }
+
+    fn sort_by_axis(primitives: &mut [Primitive], axis: usize) {
+        for p in primitives.iter_mut() {
+            p.centroid = p.bbox.centroid()[axis];
+        }
+        primitives.sort_by(|a, b| {
+            a.centroid
+                .partial_cmp(&b.centroid)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    // Finds the split with the lowest surface area heuristic cost along an axis,
+    // expects the primitives to be sorted along that axis.
+    fn best_split(primitives: &[Primitive], parent_area: f64) -> (usize, f64) {
+        let n = primitives.len();
+        let mut right_areas = vec![0.0; n];
+        let mut right_bbox = primitives[n - 1].bbox;
+        for i in (1..n).rev() {
+            right_bbox = right_bbox.surrounding(&primitives[i].bbox);
+            right_areas[i] = right_bbox.surface_area();
+        }
+
+        let mut best = (0, f64::MAX);
+        let mut left_bbox = primitives[0].bbox;
+        for i in 1..n {
+            left_bbox = left_bbox.surrounding(&primitives[i - 1].bbox);
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (left_bbox.surface_area() * i as f64 + right_areas[i] * (n - i) as f64)
+                    / parent_area;
+            if cost < best.1 {
+                best = (i, cost);
+            }
+        }
+        best
+    }
+
+    fn build(mut primitives: Vec<Primitive>) -> BvhNode {
+        let bbox = Self::bounds(&primitives);
+        let n = primitives.len();
+        if n == 1 {
+            return Self::leaf(bbox, primitives);
+        }
+
+        let parent_area = bbox.surface_area();
+        let mut best_axis = 0;
+        let mut best_split = (n / 2, f64::MAX);
+        if parent_area > 0.0 {
+            for axis in 0..3 {
+                Self::sort_by_axis(&mut primitives, axis);
+                let split = Self::best_split(&primitives, parent_area);
+                if split.1 < best_split.1 {
+                    best_axis = axis;
+                    best_split = split;
+                }
+            }
+        }
+
+        let leaf_cost = INTERSECTION_COST * n as f64;
+        if n <= MAX_LEAF_SIZE && best_split.1 >= leaf_cost {
+            return Self::leaf(bbox, primitives);
+        }
+
+        Self::sort_by_axis(&mut primitives, best_axis);
+        let right = primitives.split_off(best_split.0);
+        BvhNode::Node {
+            bbox,
+            left: Box::new(Self::build(primitives)),
+            right: Box::new(Self::build(right)),
+        }
+    }
+}
+
+impl BvhNode {
+    fn bbox(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } | BvhNode::Node { bbox, .. } => bbox,
+        }
+    }
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.bbox().hit(ray, t_min, t_max) {
+            return None;
+        }
+        match self {
+            BvhNode::Leaf { objects, .. } => {
+                let mut closest = t_max;
+                let mut rec_option = None;
+                for obj in objects {
+                    if let Some(record) = obj.hit(ray, t_min, closest) {
+                        closest = record.t;
+                        rec_option = Some(record);
+                    }
+                }
+                rec_option
+            }
+            BvhNode::Node { left, right, .. } => match left.hit(ray, t_min, t_max) {
+                Some(left_rec) => match right.hit(ray, t_min, left_rec.t) {
+                    Some(right_rec) => Some(right_rec),
+                    None => Some(left_rec),
+                },
+                None => right.hit(ray, t_min, t_max),
+            },
+        }
+    }
+}
+
+impl Hitable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut rec_option = None;
+        if let Some(ref root) = self.root {
+            if let Some(record) = root.hit(ray, t_min, closest) {
+                closest = record.t;
+                rec_option = Some(record);
+            }
+        }
+        for obj in &self.unbounded {
+            if let Some(record) = obj.hit(ray, t_min, closest) {
+                closest = record.t;
+                rec_option = Some(record);
+            }
+        }
+        rec_option
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.root.as_ref().map(|root| *root.bbox())
+    }
+}
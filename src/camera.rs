@@ -15,13 +15,8 @@ struct CameraVecs {
 
 #[derive(Clone)]
 pub struct Camera {
-    vfov: f64,
     aperture: f64,
-    vup: Vec3,
     look_from: Vec3,
-    look_at: Vec3,
-    focus_dist: f64,
-    aspect: f64,
     vecs: CameraVecs,
 }
 
@@ -50,12 +45,7 @@ impl Camera {
                 u,
                 v,
             },
-            vup,
             aperture,
-            aspect,
-            vfov,
-            focus_dist,
-            look_at,
             look_from,
         }
     }
@@ -0,0 +1,153 @@
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::image::Image;
+use crate::vec3::Vec3;
+
+// Piecewise constant 1D distribution, used to pick texels proportionally to their weight.
+struct Distribution {
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution {
+    fn new(weights: &[f64]) -> Distribution {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.0);
+        let mut total = 0.0;
+        for w in weights {
+            total += w;
+            cdf.push(total);
+        }
+        if total > 0.0 {
+            for c in cdf.iter_mut() {
+                *c /= total;
+            }
+        } else {
+            let n = weights.len() as f64;
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n;
+            }
+        }
+        Distribution { cdf, total }
+    }
+
+    fn count(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    // Probability of picking the bin `i`.
+    fn probability(&self, i: usize) -> f64 {
+        self.cdf[i + 1] - self.cdf[i]
+    }
+
+    // Returns the picked bin and the continuous position in [0, 1) inside the domain.
+    fn sample(&self, u: f64) -> (usize, f64) {
+        let i = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.count() - 1);
+        let p = self.probability(i);
+        let offset = if p > 0.0 { (u - self.cdf[i]) / p } else { 0.0 };
+        (i, (i as f64 + offset) / self.count() as f64)
+    }
+}
+
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+    rotation: f64,
+    rows: Distribution,
+    columns: Vec<Distribution>,
+}
+
+impl EnvironmentMap {
+    // Loads an equirectangular Radiance HDR image, `rotation` turns the map around
+    // the vertical axis and is given in degrees.
+    pub fn load<P: AsRef<Path>>(path: P, rotation: f64, intensity: f64) -> io::Result<Self> {
+        let mut image = Image::load_hdr(path)?;
+        for texel in image.pixels.iter_mut() {
+            *texel *= intensity;
+        }
+        Ok(Self::new(image, rotation))
+    }
+
+    fn new(image: Image, rotation: f64) -> Self {
+        let Image {
+            width,
+            height,
+            pixels: texels,
+        } = image;
+        // Rows near the poles cover a smaller solid angle, hence the sin(theta) weight.
+        let columns: Vec<Distribution> = (0..height)
+            .map(|j| {
+                let sin_theta = (PI * (j as f64 + 0.5) / height as f64).sin();
+                let weights: Vec<f64> = texels[j * width..(j + 1) * width]
+                    .iter()
+                    .map(|t| t.luminance() * sin_theta)
+                    .collect();
+                Distribution::new(&weights)
+            })
+            .collect();
+        let row_weights: Vec<f64> = columns.iter().map(|c| c.total).collect();
+        EnvironmentMap {
+            width,
+            height,
+            texels,
+            rotation: rotation.to_radians(),
+            rows: Distribution::new(&row_weights),
+            columns,
+        }
+    }
+
+    fn direction_to_uv(&self, direction: &Vec3) -> (f64, f64) {
+        let d = direction.normalized();
+        let phi = d.x().atan2(-d.z()) - self.rotation;
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = 2.0 * PI * u + self.rotation;
+        let theta = PI * v;
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    fn texel(&self, u: f64, v: f64) -> (usize, usize) {
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        (i, j)
+    }
+
+    pub fn color(&self, direction: &Vec3) -> Vec3 {
+        let (u, v) = self.direction_to_uv(direction);
+        let (i, j) = self.texel(u, v);
+        self.texels[j * self.width + i]
+    }
+
+    // Picks a direction with a probability proportional to the brightness of the map.
+    pub fn sample(&self) -> Vec3 {
+        let mut random = rand::thread_rng();
+        let (j, v) = self.rows.sample(random.gen());
+        let (_, u) = self.columns[j].sample(random.gen());
+        self.uv_to_direction(u, v)
+    }
+
+    // Solid angle density of `sample` returning `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let (i, j) = self.texel(u, v);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let texel_pdf = self.rows.probability(j) * self.columns[j].probability(i);
+        texel_pdf * (self.width * self.height) as f64 / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::tile::Tile;
+use crate::vec3::Vec3;
+
+// Luminance under which the noise of a pixel is measured against this value instead, so
+// that dark pixels do not need endless samples.
+const NOISE_FLOOR: f64 = 0.05;
+
+// Samples taken through one pixel during a pass, summed.
+pub struct PixelSamples {
+    pub color: Vec3,
+    // Sum of the squared luminance of the samples, to estimate their variance.
+    pub luminance_squares: f64,
+    // Samples hitting the world.
+    pub hits: f64,
+    pub count: u32,
+}
+
+// Pixels of one tile, filled by a single thread and merged into the film once done.
+// Colors and coverage are sums over the samples of the pixel.
+pub struct TileBuffer {
+    pub tile: Tile,
+    pixels: Vec<Vec3>,
+    luminance_squares: Vec<f64>,
+    coverage: Vec<f64>,
+    samples: Vec<u32>,
+    aovs: Vec<Vec<Vec3>>,
+}
+
+impl TileBuffer {
+    pub fn new(tile: Tile, aov_count: usize) -> TileBuffer {
+        let size = (tile.width * tile.height) as usize;
+        TileBuffer {
+            tile,
+            pixels: vec![Vec3::zero(); size],
+            luminance_squares: vec![0.0; size],
+            coverage: vec![0.0; size],
+            samples: vec![0; size],
+            aovs: vec![vec![Vec3::zero(); size]; aov_count],
+        }
+    }
+
+    // `x` and `y` are the coordinates of the pixel in the image.
+    pub fn set(&mut self, x: u32, y: u32, samples: &PixelSamples) {
+        let index = self.index(x, y);
+        self.pixels[index] = samples.color;
+        self.luminance_squares[index] = samples.luminance_squares;
+        self.coverage[index] = samples.hits;
+        self.samples[index] = samples.count;
+    }
+
+    pub fn set_aovs(&mut self, x: u32, y: u32, aovs: Vec<Vec3>) {
+        let index = self.index(x, y);
+        for (buffer, value) in self.aovs.iter_mut().zip(aovs) {
+            buffer[index] = value;
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y - self.tile.y) * self.tile.width + x - self.tile.x) as usize
+    }
+
+    pub fn sample_count(&self) -> u64 {
+        self.samples.iter().map(|&s| u64::from(s)).sum()
+    }
+}
+
+// Whole image, with the rows stored from the top. The samples of every pass are
+// accumulated, the AOVs are only set by the first one.
+pub struct Film {
+    width: u32,
+    pixels: Vec<Vec3>,
+    luminance_squares: Vec<f64>,
+    coverage: Vec<f64>,
+    samples: Vec<u32>,
+    pub aovs: Vec<Vec<Vec3>>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, aov_count: usize) -> Film {
+        let size = (width * height) as usize;
+        Film {
+            width,
+            pixels: vec![Vec3::zero(); size],
+            luminance_squares: vec![0.0; size],
+            coverage: vec![0.0; size],
+            samples: vec![0; size],
+            aovs: vec![vec![Vec3::zero(); size]; aov_count],
+        }
+    }
+
+    // Buffers created without AOVs leave the ones of the film untouched.
+    pub fn merge(&mut self, buffer: &TileBuffer) {
+        let tile = buffer.tile;
+        for row in 0..tile.height {
+            let start = ((tile.y + row) * self.width + tile.x) as usize;
+            let tile_start = (row * tile.width) as usize;
+            for i in 0..tile.width as usize {
+                self.pixels[start + i] += buffer.pixels[tile_start + i];
+                self.luminance_squares[start + i] += buffer.luminance_squares[tile_start + i];
+                self.coverage[start + i] += buffer.coverage[tile_start + i];
+                self.samples[start + i] += buffer.samples[tile_start + i];
+            }
+            for (film, tile_buffer) in self.aovs.iter_mut().zip(&buffer.aovs) {
+                film[start..start + tile.width as usize]
+                    .copy_from_slice(&tile_buffer[tile_start..tile_start + tile.width as usize]);
+            }
+        }
+    }
+
+    // Average color of the samples of each pixel.
+    pub fn pixels(&self) -> Vec<Vec3> {
+        self.pixels
+            .iter()
+            .zip(&self.samples)
+            .map(|(&p, &s)| if s > 0 { p / s as f64 } else { p })
+            .collect()
+    }
+
+    // Fraction of the samples of each pixel that hit an object.
+    pub fn coverage(&self) -> Vec<f64> {
+        self.coverage
+            .iter()
+            .zip(&self.samples)
+            .map(|(&c, &s)| if s > 0 { c / s as f64 } else { c })
+            .collect()
+    }
+
+    // Standard error of the mean luminance of each pixel, relative to that luminance.
+    // Pixels with less than two samples are infinitely noisy.
+    pub fn noise(&self) -> Vec<f64> {
+        (0..self.pixels.len())
+            .map(|i| {
+                let n = self.samples[i] as f64;
+                if n < 2.0 {
+                    return f64::INFINITY;
+                }
+                let mean = self.pixels[i].luminance() / n;
+                let variance =
+                    (self.luminance_squares[i] / n - mean * mean).max(0.0) * n / (n - 1.0);
+                (variance / n).sqrt() / mean.abs().max(NOISE_FLOOR)
+            })
+            .collect()
+    }
+
+    pub fn samples(&self) -> &[u32] {
+        &self.samples
+    }
+}
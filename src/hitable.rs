@@ -1,25 +1,79 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use crate::list::List;
+use rand::Rng;
+
+use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::utils::{onb, random_unit_vector};
 use crate::vec3::Vec3;
 
+// Identifiers of the scene object and of the material of a primitive, 0 when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Ids {
+    pub object: u32,
+    pub material: u32,
+}
+
 pub struct HitRecord<'a> {
-    t: f64,
+    pub t: f64,
     pub p: Vec3,
+    // Faces the origin of the ray, `front_face` tells whether the ray hit the outer side.
     pub normal: Vec3,
-    pub material: &'a Material,
+    pub front_face: bool,
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
+    pub ids: Ids,
+    // Primitive that was hit, to find the light reached by a ray.
+    pub hitable: &'a dyn Hitable,
+}
+
+pub type HitableArc = Arc<dyn Hitable + Send + Sync>;
+
+// Turns the outward normal of a surface toward the origin of `ray`.
+pub fn face_normal(ray: &Ray, outward_normal: Vec3) -> (Vec3, bool) {
+    if ray.direction.dot(&outward_normal) < 0.0 {
+        (outward_normal, true)
+    } else {
+        (-outward_normal, false)
+    }
+}
+
+// Whether both references point to the same primitive.
+pub fn same_hitable(a: &dyn Hitable, b: &dyn Hitable) -> bool {
+    std::ptr::eq(
+        a as *const dyn Hitable as *const u8,
+        b as *const dyn Hitable as *const u8,
+    )
 }
 
 pub trait Hitable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Picks a direction from `origin` toward the object, for the objects that can be
+    // sampled as lights.
+    fn sample_direction(&self, _origin: &Vec3) -> Option<Vec3> {
+        None
+    }
+
+    // Solid angle density of `sample_direction` returning `direction`.
+    fn direction_pdf(&self, _origin: &Vec3, _direction: &Vec3) -> f64 {
+        0.0
+    }
 }
 
 pub struct Sphere<T: Material> {
     center: Vec3,
     radius: f64,
     material: T,
+    ids: Ids,
 }
 
 impl<T: Material> Sphere<T> {
@@ -28,12 +82,25 @@ impl<T: Material> Sphere<T> {
             center,
             radius,
             material,
+            ids: Ids::default(),
         }
     }
+
+    pub fn with_ids(mut self, ids: Ids) -> Sphere<T> {
+        self.ids = ids;
+        self
+    }
+
+    // Longitude and latitude of a point on the unit sphere, mapped to [0, 1].
+    fn uv(normal: &Vec3) -> (f64, f64) {
+        let phi = (-normal.z()).atan2(normal.x()) + PI;
+        let theta = (-normal.y()).clamp(-1.0, 1.0).acos();
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl<T: Material> Hitable for Sphere<T> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.center;
         let a = ray.direction.dot(&ray.direction);
         let b = 2.0 * oc.dot(&ray.direction);
@@ -44,52 +111,81 @@ impl<T: Material> Hitable for Sphere<T> {
             let t = (-b - disc.sqrt()) / (2.0 * a);
             if t_min < t && t < t_max {
                 let p = ray.point(t);
+                let outward_normal = (p - self.center) / self.radius;
+                let (u, v) = Self::uv(&outward_normal);
+                let (normal, front_face) = face_normal(ray, outward_normal);
                 return Some(HitRecord {
                     t,
                     p,
-                    normal: (p - self.center) / self.radius,
+                    normal,
+                    front_face,
+                    u,
+                    v,
                     material: &self.material,
+                    ids: self.ids,
+                    hitable: self,
                 });
             }
             let t = (-b + disc.sqrt()) / (2.0 * a);
             if t_min < t && t < t_max {
                 let p = ray.point(t);
+                let outward_normal = (p - self.center) / self.radius;
+                let (u, v) = Self::uv(&outward_normal);
+                let (normal, front_face) = face_normal(ray, outward_normal);
                 return Some(HitRecord {
                     t,
                     p,
-                    normal: (p - self.center) / self.radius,
+                    normal,
+                    front_face,
+                    u,
+                    v,
                     material: &self.material,
+                    ids: self.ids,
+                    hitable: self,
                 });
             }
         }
         None
     }
-}
 
-impl<'a> Hitable for Arc<List<Arc<Hitable + Send + Sync>>> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut closest = t_max;
-        let mut rec_option = None;
-        for obj in self.iter() {
-            if let Some(record) = obj.hit(ray, t_min, closest) {
-                closest = record.t;
-                rec_option = Some(record);
-            }
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Samples the cone of directions under which the sphere is seen from `origin`.
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.squared_length();
+        if distance_squared <= self.radius.powi(2) {
+            return Some(random_unit_vector());
         }
-        rec_option
+        let cos_theta_max = (1.0 - self.radius.powi(2) / distance_squared).sqrt();
+        let mut random = rand::thread_rng();
+        let (r1, r2): (f64, f64) = (random.gen(), random.gen());
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let (u, v, w) = onb(&to_center);
+        Some(phi.cos() * sin_theta * u + phi.sin() * sin_theta * v + z * w)
     }
-}
 
-impl<'a> Hitable for List<Arc<Hitable + Send + Sync>> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut closest = t_max;
-        let mut rec_option = None;
-        for obj in self.iter() {
-            if let Some(record) = obj.hit(ray, t_min, closest) {
-                closest = record.t;
-                rec_option = Some(record);
-            }
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let distance_squared = (self.center - *origin).squared_length();
+        if distance_squared <= self.radius.powi(2) {
+            return 1.0 / (4.0 * PI);
+        }
+        if self
+            .hit(&Ray::new(*origin, *direction), 0.0, f64::MAX)
+            .is_none()
+        {
+            return 0.0;
         }
-        rec_option
+        let cos_theta_max = (1.0 - self.radius.powi(2) / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
     }
 }
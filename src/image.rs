@@ -0,0 +1,429 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use png::HasParameters;
+
+use crate::vec3::Vec3;
+
+fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn rgbe_to_vec3(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::zero();
+    }
+    let f = 2f64.powi(i32::from(rgbe[3]) - 136);
+    Vec3::new(
+        f64::from(rgbe[0]) * f,
+        f64::from(rgbe[1]) * f,
+        f64::from(rgbe[2]) * f,
+    )
+}
+
+fn vec3_to_rgbe(c: Vec3) -> [u8; 4] {
+    let max = c.r().max(c.g()).max(c.b());
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // Shared exponent such that the largest component is in [128, 256).
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let channel = |v: f64| (v.max(0.0) * scale).min(255.0) as u8;
+    [
+        channel(c.r()),
+        channel(c.g()),
+        channel(c.b()),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(invalid_data("unexpected end of header"));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+// Reads one scanline, either flat or with the run length encoding of the Radiance format.
+fn read_scanline<R: Read>(reader: &mut R, width: usize, out: &mut Vec<Vec3>) -> io::Result<()> {
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+    let is_rle =
+        (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !is_rle {
+        out.push(rgbe_to_vec3(first));
+        let mut rest = vec![0u8; (width - 1) * 4];
+        reader.read_exact(&mut rest)?;
+        out.extend(
+            rest.chunks(4)
+                .map(|c| rgbe_to_vec3([c[0], c[1], c[2], c[3]])),
+        );
+        return Ok(());
+    }
+    if (usize::from(first[2]) << 8 | usize::from(first[3])) != width {
+        return Err(invalid_data("scanline width mismatch"));
+    }
+
+    let mut channels = vec![0u8; width * 4];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let (run, count) = if count[0] > 128 {
+                (true, usize::from(count[0] - 128))
+            } else {
+                (false, usize::from(count[0]))
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("bad scanline data"));
+            }
+            if run {
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for i in 0..count {
+                    channels[(x + i) * 4 + channel] = value[0];
+                }
+            } else {
+                let mut values = vec![0u8; count];
+                reader.read_exact(&mut values)?;
+                for (i, value) in values.into_iter().enumerate() {
+                    channels[(x + i) * 4 + channel] = value;
+                }
+            }
+            x += count;
+        }
+    }
+    out.extend(
+        channels
+            .chunks(4)
+            .map(|c| rgbe_to_vec3([c[0], c[1], c[2], c[3]])),
+    );
+    Ok(())
+}
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    // Loads a PNG or Radiance HDR image depending on the extension of the file, 8-bit
+    // PNG images are converted from sRGB to linear values when `srgb` is set.
+    pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> io::Result<Image> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("png") => Self::load_png(path, srgb),
+            Some(e) if e.eq_ignore_ascii_case("hdr") => Self::load_hdr(path),
+            _ => Err(invalid_data(format!(
+                "unsupported image format for {}",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P, srgb: bool) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            png::ColorType::Indexed => return Err(invalid_data("unexpected indexed colors")),
+        };
+        let to_linear: Vec<f64> = (0..256)
+            .map(|i| {
+                let c = f64::from(i as u8) / 255.0;
+                if !srgb {
+                    c
+                } else if c <= 0.04045 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            })
+            .collect();
+
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let pixels = data
+            .chunks(info.line_size)
+            .take(height)
+            .flat_map(|line| line.chunks(channels).take(width))
+            .map(|p| {
+                if channels < 3 {
+                    let c = to_linear[usize::from(p[0])];
+                    Vec3::new(c, c, c)
+                } else {
+                    Vec3::new(
+                        to_linear[usize::from(p[0])],
+                        to_linear[usize::from(p[1])],
+                        to_linear[usize::from(p[2])],
+                    )
+                }
+            })
+            .collect();
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // Loads a Radiance HDR (RGBE) image, the first row of pixels is the top of the image.
+    pub fn load_hdr<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let magic = read_line(&mut reader)?;
+        if !magic.starts_with("#?") {
+            return Err(invalid_data("not a Radiance HDR file"));
+        }
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("unsupported {}", line)));
+            }
+        }
+        let resolution = read_line(&mut reader)?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", h, "+X", w] => (
+                h.parse::<usize>()
+                    .map_err(|_| invalid_data("invalid height"))?,
+                w.parse::<usize>()
+                    .map_err(|_| invalid_data("invalid width"))?,
+            ),
+            _ => {
+                return Err(invalid_data(format!(
+                    "unsupported image orientation '{}'",
+                    resolution
+                )))
+            }
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid_data("empty image"));
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for _ in 0..height {
+            read_scanline(&mut reader, width, &mut pixels)?;
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // 8-bit values of the pixels, which must already be in [0, 1], with the alpha
+    // channel after the colors when given.
+    fn bytes(&self, alpha: Option<&[f64]>) -> Vec<u8> {
+        let quantize = |c: f64| (c * 255.99) as u8;
+        self.pixels
+            .iter()
+            .enumerate()
+            .flat_map(|(i, c)| {
+                let mut pixel = vec![quantize(c.r()), quantize(c.g()), quantize(c.b())];
+                if let Some(alpha) = alpha {
+                    pixel.push(quantize(alpha[i]));
+                }
+                pixel
+            })
+            .collect()
+    }
+
+    // Writes the pixels, which must already be in [0, 1], as an 8 or 16-bit PNG image,
+    // RGBA when `alpha` is given.
+    pub fn save_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        sixteen_bit: bool,
+        alpha: Option<&[f64]>,
+    ) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set(if alpha.is_some() {
+            png::ColorType::RGBA
+        } else {
+            png::ColorType::RGB
+        });
+        let data = if sixteen_bit {
+            encoder.set(png::BitDepth::Sixteen);
+            let quantize = |c: f64| (c * 65535.99) as u16;
+            self.pixels
+                .iter()
+                .enumerate()
+                .flat_map(|(i, c)| {
+                    let mut pixel = vec![quantize(c.r()), quantize(c.g()), quantize(c.b())];
+                    if let Some(alpha) = alpha {
+                        pixel.push(quantize(alpha[i]));
+                    }
+                    pixel
+                })
+                .flat_map(|v| v.to_be_bytes().to_vec())
+                .collect()
+        } else {
+            self.bytes(alpha)
+        };
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    // Writes the pixels, which must already be in [0, 1], as a binary 8-bit PPM image.
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        file.write_all(&self.bytes(None))?;
+        file.flush()
+    }
+
+    // Writes the pixels, which must already be in [0, 1], as an uncompressed 8-bit TGA
+    // image, with an alpha channel when `alpha` is given.
+    pub fn save_tga<P: AsRef<Path>>(&self, path: P, alpha: Option<&[f64]>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let channels = if alpha.is_some() { 4 } else { 3 };
+        let mut header = [0u8; 18];
+        // Uncompressed true color image, stored from the top left corner.
+        header[2] = 2;
+        header[12..14].copy_from_slice(&(self.width as u16).to_le_bytes());
+        header[14..16].copy_from_slice(&(self.height as u16).to_le_bytes());
+        header[16] = 8 * channels as u8;
+        header[17] = 0x20 | if alpha.is_some() { 8 } else { 0 };
+        file.write_all(&header)?;
+        let mut data = self.bytes(alpha);
+        for pixel in data.chunks_mut(channels) {
+            pixel.swap(0, 2);
+        }
+        file.write_all(&data)?;
+        file.flush()
+    }
+
+    // Writes the pixels, which must already be in [0, 1], as a 24-bit BMP image.
+    pub fn save_bmp<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        // Rows are padded to a multiple of 4 bytes and stored from the bottom.
+        let row_size = (self.width * 3).div_ceil(4) * 4;
+        let data_size = row_size * self.height;
+        let offset = 14 + 40;
+        file.write_all(b"BM")?;
+        file.write_all(&((offset + data_size) as u32).to_le_bytes())?;
+        file.write_all(&[0; 4])?;
+        file.write_all(&(offset as u32).to_le_bytes())?;
+        file.write_all(&40u32.to_le_bytes())?;
+        file.write_all(&(self.width as i32).to_le_bytes())?;
+        file.write_all(&(self.height as i32).to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&24u16.to_le_bytes())?;
+        file.write_all(&[0; 24])?;
+        let data = self.bytes(None);
+        for row in data.chunks(self.width * 3).rev() {
+            let mut line: Vec<u8> = row.chunks(3).flat_map(|p| vec![p[2], p[1], p[0]]).collect();
+            line.resize(row_size, 0);
+            file.write_all(&line)?;
+        }
+        file.flush()
+    }
+
+    // Writes the linear pixels as a flat Radiance HDR (RGBE) image.
+    pub fn save_hdr<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(
+            file,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+        for pixel in &self.pixels {
+            file.write_all(&vec3_to_rgbe(*pixel))?;
+        }
+        file.flush()
+    }
+}
+
+// Writes an uncompressed single part scanline OpenEXR image with 32-bit float channels,
+// given as a name and one value per pixel with the first row at the top of the image.
+pub fn save_exr<P: AsRef<Path>>(
+    path: P,
+    width: usize,
+    height: usize,
+    channels: &mut [(String, Vec<f32>)],
+) -> io::Result<()> {
+    // Channels are stored in alphabetical order.
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&20_000_630u32.to_le_bytes());
+    header.extend_from_slice(&2u32.to_le_bytes());
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    let mut channel_list = Vec::new();
+    for (name, _) in channels.iter() {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        // FLOAT pixel type, linear, reserved bytes and sampling of 1 in x and y.
+        channel_list.extend_from_slice(&2u32.to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1u32.to_le_bytes());
+        channel_list.extend_from_slice(&1u32.to_le_bytes());
+    }
+    channel_list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect();
+    attribute("channels", "chlist", &channel_list);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+
+    // Offset table, each scanline holds its y coordinate, its size and the values of
+    // every channel one after the other.
+    let line_size = channels.len() * width * 4;
+    let table_size = height * 8;
+    for y in 0..height {
+        let offset = header.len() + table_size + y * (8 + line_size);
+        file.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(line_size as u32).to_le_bytes())?;
+        for (_, values) in channels.iter() {
+            for value in &values[y * width..(y + 1) * width] {
+                file.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
@@ -0,0 +1,300 @@
+use std::f64;
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::background::Background;
+use crate::hitable::{same_hitable, HitRecord, Hitable};
+use crate::light::LightList;
+use crate::ray::Ray;
+use crate::utils::random_unit_vector;
+use crate::vec3::Vec3;
+
+// Everything an integrator needs to know about the scene being rendered.
+pub struct SceneContext<'a> {
+    pub world: &'a (dyn Hitable + Send + Sync),
+    pub lights: &'a LightList,
+    pub background: &'a Background,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+}
+
+// Computes the color seen along a camera ray, and whether the ray hit the world, which
+// gives the coverage of the alpha channel.
+pub trait Integrator {
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool);
+}
+
+pub type IntegratorArc = Arc<dyn Integrator + Send + Sync>;
+
+// Returns the integrator called `name` on the command line, for the integrators
+// without settings.
+pub fn from_name(name: &str) -> Option<IntegratorArc> {
+    let integrator: IntegratorArc = match name {
+        "path" => Arc::new(PathTracer),
+        "normals" => Arc::new(Normals),
+        "depth" => Arc::new(Depth::new(10.0)),
+        "albedo" => Arc::new(Albedo),
+        _ => return None,
+    };
+    Some(integrator)
+}
+
+// Unidirectional path tracing with next event estimation.
+pub struct PathTracer;
+
+impl PathTracer {
+    // Picks the next direction of the path with its weight. Half of the diffuse rays are
+    // sent toward the bright parts of an environment map, the choice being made before
+    // the material scatters so that the map is sampled even when the material absorbs
+    // its own sample, and the contributions are weighted with the density of both
+    // strategies. Also returns the density of the scattered direction, None for
+    // specular materials. None when the path ends.
+    fn scatter(
+        scene: &SceneContext,
+        ray: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Ray, Vec3, Option<f64>)> {
+        let material = hit_record.material;
+        // Only non specular materials have a density, whatever the direction asked for.
+        let probe = Ray::new(hit_record.p, hit_record.normal);
+        let diffuse = material.scattering_pdf(ray, hit_record, &probe).is_some();
+        if let (Background::Environment(ref map), true) = (scene.background, diffuse) {
+            let scattered = if random::<bool>() {
+                Ray::new(hit_record.p, map.sample())
+            } else {
+                material.scatter(ray, hit_record)?.scattered
+            };
+            let pdf = Self::scattering_pdf(scene, ray, hit_record, &scattered);
+            if pdf <= 0.0 {
+                return None;
+            }
+            let attenuation = material.eval(ray, hit_record, &scattered) / pdf;
+            return Some((scattered, attenuation, Some(pdf)));
+        }
+        let scatter_record = material.scatter(ray, hit_record)?;
+        let pdf = material.scattering_pdf(ray, hit_record, &scatter_record.scattered);
+        Some((scatter_record.scattered, scatter_record.attenuation, pdf))
+    }
+
+    // Density of the scattered directions of a non specular material, including the
+    // directions sent toward the environment map.
+    fn scattering_pdf(
+        scene: &SceneContext,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let material_pdf = hit_record
+            .material
+            .scattering_pdf(ray, hit_record, scattered)
+            .unwrap_or(0.0);
+        match scene.background {
+            Background::Environment(ref map) => {
+                0.5 * material_pdf + 0.5 * map.pdf(&scattered.direction)
+            }
+            _ => material_pdf,
+        }
+    }
+
+    // Power heuristic weight of a sampling strategy against another one.
+    fn mis_weight(pdf: f64, other_pdf: f64) -> f64 {
+        let pdf = pdf * pdf;
+        let other_pdf = other_pdf * other_pdf;
+        if pdf + other_pdf > 0.0 {
+            pdf / (pdf + other_pdf)
+        } else {
+            0.0
+        }
+    }
+
+    // Direct lighting at a non specular bounce, from a direction sampled toward the
+    // lights. The light could also have been reached by the scattered ray, so the
+    // contribution is weighted against the scattering density. Specular materials
+    // cannot be reached by a sampled direction and get nothing.
+    fn sample_lights(scene: &SceneContext, ray: &Ray, hit_record: &HitRecord) -> Vec3 {
+        let (light, direction) = match scene.lights.sample(&hit_record.p) {
+            Some(sample) => sample,
+            None => return Vec3::zero(),
+        };
+        let shadow_ray = Ray::new(hit_record.p, direction);
+        if hit_record
+            .material
+            .scattering_pdf(ray, hit_record, &shadow_ray)
+            .is_none()
+        {
+            return Vec3::zero();
+        }
+        let bsdf = hit_record.material.eval(ray, hit_record, &shadow_ray);
+        if bsdf.squared_length() == 0.0 {
+            return Vec3::zero();
+        }
+        let pdf = scene.lights.direction_pdf(light, &hit_record.p, &direction);
+        if pdf <= 0.0 {
+            return Vec3::zero();
+        }
+        let weight = Self::mis_weight(
+            pdf,
+            Self::scattering_pdf(scene, ray, hit_record, &shadow_ray),
+        );
+        match scene.world.hit(&shadow_ray, 0.001, f64::MAX) {
+            Some(light_record) if same_hitable(light_record.hitable, light) => {
+                weight * bsdf * light_record.material.emitted(&light_record) / pdf
+            }
+            _ => Vec3::zero(),
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    // Follows the path of a camera ray through the scene. `throughput` is the weight
+    // of the light reaching the current vertex in the pixel.
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool) {
+        let mut ray = *ray;
+        let mut radiance = Vec3::zero();
+        let mut throughput = Vec3::one();
+        // Density with which the previous bounce picked the direction of `ray`, None
+        // for camera rays and specular bounces. Lights were then also sampled
+        // explicitly, so their emission is weighted against that strategy.
+        let mut scattering_pdf = None;
+        let mut depth = 0;
+        let mut hit = false;
+        loop {
+            let hit_record = match scene.world.hit(&ray, 0.001, f64::MAX) {
+                Some(hit_record) => hit_record,
+                None => {
+                    radiance += throughput * scene.background.color(&ray);
+                    break;
+                }
+            };
+            hit = true;
+            let material = hit_record.material;
+            let mut emitted = material.emitted(&hit_record);
+            if let Some(pdf) = scattering_pdf {
+                if material.is_emissive() && !scene.lights.is_empty() {
+                    let light_pdf =
+                        scene
+                            .lights
+                            .direction_pdf(hit_record.hitable, &ray.origin, &ray.direction);
+                    emitted *= Self::mis_weight(pdf, light_pdf);
+                }
+            }
+            radiance += throughput * emitted;
+            if depth >= scene.max_depth {
+                break;
+            }
+            // Lights are sampled even when the scattered ray is absorbed, as when a fuzzy
+            // metal reflects below its surface, or the direct lighting would be too dark.
+            radiance += throughput * Self::sample_lights(scene, &ray, &hit_record);
+            let (scattered, attenuation, pdf) = match Self::scatter(scene, &ray, &hit_record) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+            // Directions of the map below the surface carry nothing.
+            if attenuation.max_component() <= 0.0 {
+                break;
+            }
+            throughput = throughput * attenuation;
+            // Dim paths are terminated more often, the surviving ones are brightened
+            // accordingly so that the result stays unbiased.
+            if depth >= scene.roulette_depth {
+                let survival = throughput.max_component().min(1.0);
+                if random::<f64>() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = scattered;
+            scattering_pdf = pdf;
+            depth += 1;
+        }
+        (radiance, hit)
+    }
+}
+
+// Shading normals of the first hit, mapped from [-1, 1] to [0, 1].
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool) {
+        match scene.world.hit(ray, 0.001, f64::MAX) {
+            Some(hit_record) => (0.5 * (hit_record.normal.normalized() + 1.0), true),
+            None => (Vec3::zero(), false),
+        }
+    }
+}
+
+// Distance to the first hit, white at the camera and black from `max_distance`.
+pub struct Depth {
+    max_distance: f64,
+}
+
+impl Depth {
+    pub fn new(max_distance: f64) -> Depth {
+        Depth { max_distance }
+    }
+}
+
+impl Integrator for Depth {
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool) {
+        match scene.world.hit(ray, 0.001, f64::MAX) {
+            Some(hit_record) => {
+                let distance = hit_record.t * ray.direction.length();
+                let depth = Vec3::one() * (1.0 - distance / self.max_distance).max(0.0);
+                (depth, true)
+            }
+            None => (Vec3::zero(), false),
+        }
+    }
+}
+
+// Albedo of the material of the first hit, without any lighting.
+pub struct Albedo;
+
+impl Integrator for Albedo {
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool) {
+        match scene.world.hit(ray, 0.001, f64::MAX) {
+            Some(hit_record) => (hit_record.material.albedo(&hit_record), true),
+            None => (Vec3::zero(), false),
+        }
+    }
+}
+
+// Fraction of the hemisphere above the first hit that is not occluded by the scene
+// within `max_distance`, in greyscale. Materials are ignored, every object is opaque.
+pub struct AmbientOcclusion {
+    rays: u32,
+    max_distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(rays: u32, max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion { rays, max_distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn color(&self, scene: &SceneContext, ray: &Ray) -> (Vec3, bool) {
+        let hit_record = match scene.world.hit(ray, 0.001, f64::MAX) {
+            Some(hit_record) => hit_record,
+            None => return (Vec3::one(), false),
+        };
+        if self.rays == 0 {
+            return (Vec3::one(), true);
+        }
+        // Hit normals face the ray, so the occlusion is computed on the visible side.
+        let normal = hit_record.normal.normalized();
+        // Cosine weighted directions, so that the occlusion matches a diffuse surface
+        // lit by a uniform sky.
+        let unoccluded = (0..self.rays)
+            .filter(|_| {
+                let direction = (normal + random_unit_vector()).normalized();
+                scene
+                    .world
+                    .hit(&Ray::new(hit_record.p, direction), 0.001, self.max_distance)
+                    .is_none()
+            })
+            .count();
+        (Vec3::one() * (unoccluded as f64 / self.rays as f64), true)
+    }
+}
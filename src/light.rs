@@ -0,0 +1,42 @@
+use rand::Rng;
+
+use crate::hitable::{Hitable, HitableArc};
+use crate::vec3::Vec3;
+
+// Emissive objects of the world that are sampled explicitly at each diffuse bounce.
+#[derive(Clone, Default)]
+pub struct LightList {
+    lights: Vec<HitableArc>,
+}
+
+impl LightList {
+    pub fn new(lights: Vec<HitableArc>) -> LightList {
+        LightList { lights }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // Picks one of the lights uniformly and a direction from `origin` toward it.
+    pub fn sample(&self, origin: &Vec3) -> Option<(&dyn Hitable, Vec3)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let index = rand::thread_rng().gen_range(0, self.lights.len());
+        let light = &*self.lights[index];
+        light
+            .sample_direction(origin)
+            .map(|direction| (light as &dyn Hitable, direction))
+    }
+
+    // Density of `sample` returning `light` and `direction`. Only the samples reaching
+    // the light they were taken on count, so that other lights in the same direction
+    // are left out and the cost does not depend on the number of lights.
+    pub fn direction_pdf(&self, light: &dyn Hitable, origin: &Vec3, direction: &Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        light.direction_pdf(origin, direction) / self.lights.len() as f64
+    }
+}
@@ -1,16 +1,41 @@
 #[macro_use]
 extern crate derive_builder;
 
+use crate::aov::Aov;
+use crate::integrator::AmbientOcclusion;
+use crate::progress::ProgressCallback;
 use crate::raytracer::RaytracerBuilder;
+use crate::scene::Scene;
+use crate::tile::TileOrder;
+use crate::tonemap::ToneMapper;
 use clap::value_t;
 use clap::App;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod aabb;
+mod aov;
+mod background;
+mod bvh;
 mod camera;
+mod environment;
+mod film;
 mod hitable;
-mod list;
+mod image;
+mod integrator;
+mod light;
 mod material;
+mod obj;
+mod perlin;
+mod progress;
 mod ray;
 mod raytracer;
+mod scene;
+mod texture;
+mod tile;
+mod tonemap;
+mod triangle;
 mod utils;
 mod vec3;
 
@@ -21,14 +46,133 @@ fn main() {
                         "<FILE> 'Sets the output image file path'
                          -x <x> 'Sets the width of the output image, default to 2000'
                          -y <Y> 'Set the height of the output image, default to 1000' 
-                         [-t] 'Sets the number of threads to use, by default the number of cores availables'")
+                         [-t] 'Sets the number of threads to use, by default the number of cores availables'
+                         --scene [SCENE] 'Loads the scene to render from a scene description file'
+                         --integrator [INTEGRATOR] 'Sets the rendering algorithm: path (default), normals, depth, albedo or ao'
+                         --ao-rays [RAYS] 'Sets the number of occlusion rays per sample of the ao integrator, default to 16'
+                         --ao-distance [DISTANCE] 'Sets the distance up to which the ao integrator looks for occluders, unlimited by default'
+                         --aov [AOVS] 'Also writes the comma separated passes among depth, normal, albedo, object_id, material_id and position'
+                         --exposure [STOPS] 'Scales the colors of PNG, PPM, TGA and BMP images by 2^STOPS, default to 0'
+                         --tonemap [OPERATOR] 'Sets the tone mapping of PNG, PPM, TGA and BMP images: clamp (default), reinhard or aces'
+                         --bit-depth [BITS] 'Sets the bits per channel of PNG images, 8 (default) or 16'
+                         --alpha 'Adds an alpha channel with the coverage of the objects to PNG, TGA and OpenEXR images'
+                         --tile-size [SIZE] 'Sets the width and height of the tiles rendered by the threads, default to 32'
+                         --tile-order [ORDER] 'Sets the order of the tiles: spiral (default), hilbert or scanline'
+                         --progressive 'Renders in passes of 1, 2, 4... samples per pixel, writing the image after each one'
+                         --samples [SAMPLES] 'Sets the samples per pixel, at most when the render may stop early, default to 100'
+                         --time-limit [SECONDS] 'Stops the render after this time, keeping the samples rendered so far'
+                         --target-noise [NOISE] 'Stops sampling the pixels whose relative noise is under NOISE, such as 0.01'")
                     .get_matches();
 
     let mut raytracer = RaytracerBuilder::default();
     let x = value_t!(matches.value_of("x"), u32).expect("x should be a number");
     let y = value_t!(matches.value_of("y"), u32).expect("y should be a number");
 
+    if let Some(path) = matches.value_of("scene") {
+        let scene = Scene::load(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        });
+        if let Some(camera) = scene.camera(x as f64 / y as f64) {
+            raytracer.camera(camera);
+        }
+        if let Some(background) = scene.background {
+            raytracer.background(background);
+        }
+        raytracer.world(scene.world).lights(scene.lights);
+    }
+
+    if let Some(name) = matches.value_of("integrator") {
+        let integrator = if name == "ao" {
+            let rays = matches
+                .value_of("ao-rays")
+                .map_or(16, |v| v.parse().expect("ao-rays should be a number"));
+            let distance = matches.value_of("ao-distance").map_or(f64::MAX, |v| {
+                v.parse().expect("ao-distance should be a number")
+            });
+            Arc::new(AmbientOcclusion::new(rays, distance))
+        } else {
+            integrator::from_name(name).unwrap_or_else(|| {
+                eprintln!("Unknown integrator: {}", name);
+                process::exit(1);
+            })
+        };
+        raytracer.integrator(integrator);
+    }
+
+    if let Some(names) = matches.value_of("aov") {
+        let aovs = names
+            .split(',')
+            .map(|name| {
+                Aov::from_name(name.trim()).unwrap_or_else(|| {
+                    eprintln!("Unknown aov: {}", name);
+                    process::exit(1);
+                })
+            })
+            .collect();
+        raytracer.aovs(aovs);
+    }
+
+    if let Some(exposure) = matches.value_of("exposure") {
+        raytracer.exposure(exposure.parse().expect("exposure should be a number"));
+    }
+
+    if let Some(name) = matches.value_of("tonemap") {
+        let tone_mapper = ToneMapper::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown tone mapping operator: {}", name);
+            process::exit(1);
+        });
+        raytracer.tone_mapper(tone_mapper);
+    }
+
+    if let Some(bits) = matches.value_of("bit-depth") {
+        match bits {
+            "8" => raytracer.bit_depth(8),
+            "16" => raytracer.bit_depth(16),
+            _ => {
+                eprintln!("Unsupported bit depth: {}", bits);
+                process::exit(1);
+            }
+        };
+    }
+
+    if let Some(size) = matches.value_of("tile-size") {
+        raytracer.tile_size(size.parse().expect("tile-size should be a number"));
+    }
+
+    if let Some(name) = matches.value_of("tile-order") {
+        let order = TileOrder::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown tile order: {}", name);
+            process::exit(1);
+        });
+        raytracer.tile_order(order);
+    }
+
+    if let Some(samples) = matches.value_of("samples") {
+        raytracer.antialiasing_samples(samples.parse().expect("samples should be a number"));
+    }
+
+    if let Some(seconds) = matches.value_of("time-limit") {
+        let value: f64 = seconds.parse().expect("time-limit should be a number");
+        let limit = Duration::try_from_secs_f64(value).unwrap_or_else(|_| {
+            eprintln!("Invalid time limit: {}", seconds);
+            process::exit(1);
+        });
+        raytracer.time_limit(limit);
+    }
+
+    if let Some(noise) = matches.value_of("target-noise") {
+        raytracer.target_noise(
+            noise
+                .parse::<f64>()
+                .expect("target-noise should be a number"),
+        );
+    }
+
     raytracer
+        .progress(Arc::new(progress::print) as ProgressCallback)
+        .alpha(matches.is_present("alpha"))
+        .progressive(matches.is_present("progressive"))
         .res_x(x)
         .res_y(y)
         .out_file(matches.value_of("FILE").unwrap())
@@ -1,8 +1,11 @@
 use rand::random;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 use crate::hitable::HitRecord;
 use crate::ray::Ray;
-use crate::utils::random_in_unit_sphere;
+use crate::texture::{ConstantTexture, TextureArc};
+use crate::utils::{random_in_unit_sphere, random_unit_vector};
 use crate::vec3::Vec3;
 
 fn reflect(v: Vec3, n: Vec3) -> Vec3 {
@@ -32,16 +35,78 @@ pub struct ScatterRecord {
 
 pub trait Material {
     fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord>;
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Base color of the surface, used by the debug integrators.
+    fn albedo(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::zero()
+    }
+
+    // Value of the BSDF for the incoming and scattered directions, multiplied by the
+    // cosine between the scattered direction and the normal.
+    fn eval(&self, _r_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> Vec3 {
+        Vec3::zero()
+    }
+
+    // Density of `scatter` picking the direction of `scattered`, None when the
+    // material scatters in a single direction and cannot be importance sampled.
+    fn scattering_pdf(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> Option<f64> {
+        None
+    }
+}
+
+pub type MaterialArc = Arc<dyn Material + Send + Sync>;
+
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        (**self).scatter(r_in, hit_record)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
+        (**self).emitted(hit_record)
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        (**self).albedo(hit_record)
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Vec3 {
+        (**self).eval(r_in, hit_record, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        (**self).scattering_pdf(r_in, hit_record, scattered)
+    }
 }
 
 pub struct Metal {
-    albedo: Vec3,
+    albedo: TextureArc,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Vec3, fuzz: f64) -> Metal {
-        let fuzz = fuzz.max(0.0).min(1.0);
+        Self::textured(Arc::new(ConstantTexture::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: TextureArc, fuzz: f64) -> Metal {
+        let fuzz = fuzz.clamp(0.0, 1.0);
         Metal { albedo, fuzz }
     }
 }
@@ -53,7 +118,7 @@ impl Material for Metal {
             hit_record.p,
             reflected + self.fuzz * random_in_unit_sphere(),
         );
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
         if scattered.direction.dot(&hit_record.normal) > 0.0 {
             Some(ScatterRecord {
                 scattered,
@@ -63,27 +128,79 @@ impl Material for Metal {
             None
         }
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p)
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Vec3 {
+        match self.scattering_pdf(r_in, hit_record, scattered) {
+            Some(pdf) => self.albedo.value(hit_record.u, hit_record.v, &hit_record.p) * pdf,
+            None => Vec3::zero(),
+        }
+    }
+
+    // The scattered direction points toward a uniform point of the ball of radius
+    // `fuzz` around the mirror direction, its density is the volume of the ball seen
+    // in a solid angle around the direction.
+    fn scattering_pdf(&self, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+        let direction = scattered.direction.normalized();
+        if direction.dot(&hit_record.normal) <= 0.0 {
+            return Some(0.0);
+        }
+        let reflected = reflect(r_in.direction.normalized(), hit_record.normal);
+        let b = direction.dot(&reflected);
+        let discriminant = b * b - (1.0 - self.fuzz.powi(2));
+        if discriminant <= 0.0 || b <= 0.0 {
+            return Some(0.0);
+        }
+        let t_far = b + discriminant.sqrt();
+        let t_near = (b - discriminant.sqrt()).max(0.0);
+        let volume = 4.0 / 3.0 * PI * self.fuzz.powi(3);
+        Some((t_far.powi(3) - t_near.powi(3)) / (3.0 * volume))
+    }
 }
 
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: TextureArc,
 }
 
 impl Lambertian {
     pub fn new(albedo: Vec3) -> Self {
+        Self::textured(Arc::new(ConstantTexture::new(albedo)))
+    }
+
+    pub fn textured(albedo: TextureArc) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let target = hit_record.p + hit_record.normal + random_in_unit_sphere();
+        let target = hit_record.p + hit_record.normal + random_unit_vector();
         let scattered = Ray::new(hit_record.p, target - hit_record.p);
         Some(ScatterRecord {
             scattered,
-            attenuation: self.albedo,
+            attenuation: self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
         })
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p)
+    }
+
+    fn eval(&self, _r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Vec3 {
+        let cosine = hit_record.normal.dot(&scattered.direction.normalized());
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p) * cosine.max(0.0) / PI
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let cosine = hit_record.normal.dot(&scattered.direction.normalized());
+        Some(cosine.max(0.0) / PI)
+    }
 }
 
 pub struct Dielectric {
@@ -98,22 +215,21 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let outward_normal;
         let ni_over_nt;
         let cosine;
         let reflected = reflect(r_in.direction, hit_record.normal);
         let attenuation = Vec3::one();
-        if r_in.direction.dot(&hit_record.normal) > 0.0 {
-            outward_normal = -hit_record.normal;
-            ni_over_nt = self.ref_ind;
-            cosine =
-                self.ref_ind * r_in.direction.dot(&hit_record.normal) / r_in.direction.length();
-        } else {
-            outward_normal = hit_record.normal;
+        // The normal faces the ray, the side that was hit tells whether it enters or
+        // leaves the material.
+        let incidence = -r_in.direction.dot(&hit_record.normal) / r_in.direction.length();
+        if hit_record.front_face {
             ni_over_nt = 1.0 / self.ref_ind;
-            cosine = -r_in.direction.dot(&hit_record.normal) / r_in.direction.length();
+            cosine = incidence;
+        } else {
+            ni_over_nt = self.ref_ind;
+            cosine = self.ref_ind * incidence;
         }
-        match refract(r_in.direction, outward_normal, ni_over_nt) {
+        match refract(r_in.direction, hit_record.normal, ni_over_nt) {
             Some(refracted) => {
                 let reflect_prob = schlick(cosine, self.ref_ind);
                 let scatter_dir = if random::<f64>() < reflect_prob {
@@ -132,4 +248,36 @@ impl Material for Dielectric {
             }),
         }
     }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::one()
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _hit_record: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Vec3 {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Vec3 {
+        self.emit
+    }
 }
@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, MaterialArc, Metal};
+use crate::texture::{Filter, ImageTexture, TextureArc, Wrap};
+use crate::triangle::{Mesh, MeshFace};
+use crate::vec3::Vec3;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(file, e) => write!(f, "could not read {}: {}", file.display(), e),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))
+}
+
+struct LineParser<'a> {
+    file: &'a Path,
+    line: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn error<S: Into<String>>(&self, message: S) -> ObjError {
+        ObjError::Parse {
+            file: self.file.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn number(&self, token: Option<&str>) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error("missing value"))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("expected a number, found '{}'", token)))
+    }
+
+    fn vec3<'b, I: Iterator<Item = &'b str>>(&self, tokens: &mut I) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.number(tokens.next())?,
+            self.number(tokens.next())?,
+            self.number(tokens.next())?,
+        ))
+    }
+
+    fn name<'b, I: Iterator<Item = &'b str>>(&self, tokens: I) -> Result<String, ObjError> {
+        let name = tokens.collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            Err(self.error("missing name"))
+        } else {
+            Ok(name)
+        }
+    }
+
+    // Resolves a 1-based, possibly negative, obj index into the current buffer.
+    fn index(&self, token: &str, len: usize, kind: &str) -> Result<usize, ObjError> {
+        let index: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("malformed face, invalid index '{}'", token)))?;
+        let resolved = if index < 0 {
+            len as i64 + index
+        } else {
+            index - 1
+        };
+        if index == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!(
+                "malformed face, {} index {} is out of range",
+                kind, index
+            )));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+#[derive(Default)]
+struct MtlMaterial {
+    diffuse: Option<Vec3>,
+    diffuse_map: Option<TextureArc>,
+    specular: Option<Vec3>,
+    emission: Option<Vec3>,
+    shininess: Option<f64>,
+    ref_ind: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+}
+
+impl MtlMaterial {
+    fn build(&self) -> MaterialArc {
+        let diffuse = self.diffuse.unwrap_or_else(|| Vec3::new(0.8, 0.8, 0.8));
+        let specular = self.specular.unwrap_or_else(Vec3::zero);
+        let transparent = self.dissolve.is_some_and(|d| d < 1.0);
+        if let Some(emission) = self.emission {
+            if emission.squared_length() > 0.0 {
+                return Arc::new(DiffuseLight::new(emission));
+            }
+        }
+        match self.illum {
+            Some(4) | Some(6) | Some(7) | Some(9) => {
+                Arc::new(Dielectric::new(self.ref_ind.unwrap_or(1.5)))
+            }
+            _ if transparent => Arc::new(Dielectric::new(self.ref_ind.unwrap_or(1.5))),
+            Some(3) => Arc::new(Metal::new(specular, self.fuzz())),
+            _ if self.diffuse_map.is_none()
+                && diffuse.squared_length() == 0.0
+                && specular.squared_length() > 0.0 =>
+            {
+                Arc::new(Metal::new(specular, self.fuzz()))
+            }
+            _ => match self.diffuse_map {
+                Some(ref map) => Arc::new(Lambertian::textured(map.clone())),
+                None => Arc::new(Lambertian::new(diffuse)),
+            },
+        }
+    }
+
+    // Roughness matching the Phong specular exponent.
+    fn fuzz(&self) -> f64 {
+        let ns = self.shininess.unwrap_or(1000.0).max(0.0);
+        (2.0 / (ns + 2.0)).sqrt()
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, MaterialArc>, ObjError> {
+    let src = read(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        let parser = LineParser {
+            file: path,
+            line: i + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        if keyword == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.build());
+            }
+            current = Some((parser.name(tokens)?, MtlMaterial::default()));
+            continue;
+        }
+        let mtl = match current {
+            Some((_, ref mut mtl)) => mtl,
+            None => return Err(parser.error(format!("'{}' before any 'newmtl'", keyword))),
+        };
+        match keyword {
+            "Kd" => mtl.diffuse = Some(parser.vec3(&mut tokens)?),
+            "map_Kd" => {
+                // Options such as -s or -o are not supported, the file name comes last.
+                let file = tokens
+                    .last()
+                    .ok_or_else(|| parser.error("missing file name"))?;
+                let image = Image::load(dir.join(file), true)
+                    .map_err(|e| parser.error(format!("could not load texture {}: {}", file, e)))?;
+                mtl.diffuse_map = Some(Arc::new(ImageTexture::new(
+                    Arc::new(image),
+                    Wrap::Repeat,
+                    Filter::Bilinear,
+                )));
+            }
+            "Ks" => mtl.specular = Some(parser.vec3(&mut tokens)?),
+            "Ke" => mtl.emission = Some(parser.vec3(&mut tokens)?),
+            "Ns" => mtl.shininess = Some(parser.number(tokens.next())?),
+            "Ni" => mtl.ref_ind = Some(parser.number(tokens.next())?),
+            "d" => mtl.dissolve = Some(parser.number(tokens.next())?),
+            "Tr" => mtl.dissolve = Some(1.0 - parser.number(tokens.next())?),
+            "illum" => mtl.illum = Some(parser.number(tokens.next())? as u32),
+            _ => {}
+        }
+    }
+    if let Some((name, mtl)) = current {
+        materials.insert(name, mtl.build());
+    }
+    Ok(materials)
+}
+
+struct Group {
+    material: MaterialArc,
+    faces: Vec<MeshFace>,
+}
+
+// Loads the triangles of an obj file as one mesh per material, faces that do not
+// use any material from the mtl libraries get the default material.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    default_material: MaterialArc,
+) -> Result<Vec<Mesh<MaterialArc>>, ObjError> {
+    let path = path.as_ref();
+    let src = read(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut materials: HashMap<String, MaterialArc> = HashMap::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut group_ids: HashMap<String, usize> = HashMap::new();
+    let mut current_group: Option<usize> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        let parser = LineParser {
+            file: path,
+            line: i + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        match keyword {
+            "v" => vertices.push(parser.vec3(&mut tokens)?),
+            "vn" => normals.push(parser.vec3(&mut tokens)?),
+            "vt" => uvs.push((parser.number(tokens.next())?, parser.number(tokens.next())?)),
+            "mtllib" => {
+                for file in tokens {
+                    // Unreadable libraries are reported at the line referencing them.
+                    let library = load_mtl(&dir.join(file)).map_err(|e| match e {
+                        ObjError::Io(..) => parser.error(e.to_string()),
+                        e => e,
+                    })?;
+                    materials.extend(library);
+                }
+            }
+            "usemtl" => {
+                let name = parser.name(tokens)?;
+                let material = materials
+                    .get(&name)
+                    .ok_or_else(|| parser.error(format!("unknown material '{}'", name)))?;
+                let id = *group_ids.entry(name).or_insert_with(|| {
+                    groups.push(Group {
+                        material: material.clone(),
+                        faces: Vec::new(),
+                    });
+                    groups.len() - 1
+                });
+                current_group = Some(id);
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for corner in tokens {
+                    let mut indices = corner.split('/');
+                    let vertex = parser.index(indices.next().unwrap(), vertices.len(), "vertex")?;
+                    let uv = match indices.next() {
+                        Some(t) if !t.is_empty() => Some(parser.index(t, uvs.len(), "uv")?),
+                        _ => None,
+                    };
+                    let normal = match indices.next() {
+                        Some(n) if !n.is_empty() => {
+                            Some(parser.index(n, normals.len(), "normal")?)
+                        }
+                        _ => None,
+                    };
+                    corners.push((vertex, uv, normal));
+                }
+                if corners.len() < 3 {
+                    return Err(parser.error(format!(
+                        "malformed face, expected at least 3 vertices, found {}",
+                        corners.len()
+                    )));
+                }
+                let group = match current_group {
+                    Some(id) => id,
+                    None => {
+                        groups.push(Group {
+                            material: default_material.clone(),
+                            faces: Vec::new(),
+                        });
+                        current_group = Some(groups.len() - 1);
+                        groups.len() - 1
+                    }
+                };
+                // Triangulates polygons as a fan around their first vertex.
+                for k in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[k], corners[k + 1]];
+                    let uvs = match (tri[0].1, tri[1].1, tri[2].1) {
+                        (Some(t0), Some(t1), Some(t2)) => Some([t0, t1, t2]),
+                        _ => None,
+                    };
+                    let normals = match (tri[0].2, tri[1].2, tri[2].2) {
+                        (Some(n0), Some(n1), Some(n2)) => Some([n0, n1, n2]),
+                        _ => None,
+                    };
+                    groups[group].faces.push(MeshFace {
+                        vertices: [tri[0].0, tri[1].0, tri[2].0],
+                        normals,
+                        uvs,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|group| !group.faces.is_empty())
+        .map(|group| compact(&vertices, &normals, &uvs, group))
+        .collect())
+}
+
+// Maps indices of a shared obj buffer to indices of a smaller buffer that only
+// holds the referenced elements.
+struct Remap<'a, T: Copy> {
+    source: &'a [T],
+    indices: HashMap<usize, usize>,
+    values: Vec<T>,
+}
+
+impl<'a, T: Copy> Remap<'a, T> {
+    fn new(source: &'a [T]) -> Self {
+        Remap {
+            source,
+            indices: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+
+    fn map(&mut self, indices: [usize; 3]) -> [usize; 3] {
+        let mut mapped = [0; 3];
+        for (i, &index) in indices.iter().enumerate() {
+            let source = self.source;
+            let values = &mut self.values;
+            mapped[i] = *self.indices.entry(index).or_insert_with(|| {
+                values.push(source[index]);
+                values.len() - 1
+            });
+        }
+        mapped
+    }
+}
+
+// Only keeps the vertices, normals and uvs referenced by the faces of a group.
+fn compact(
+    vertices: &[Vec3],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+    group: Group,
+) -> Mesh<MaterialArc> {
+    let mut vertices = Remap::new(vertices);
+    let mut normals = Remap::new(normals);
+    let mut uvs = Remap::new(uvs);
+
+    let faces = group
+        .faces
+        .iter()
+        .map(|face| MeshFace {
+            vertices: vertices.map(face.vertices),
+            normals: face.normals.map(|indices| normals.map(indices)),
+            uvs: face.uvs.map(|indices| uvs.map(indices)),
+        })
+        .collect();
+
+    Mesh::new(
+        vertices.values,
+        normals.values,
+        uvs.values,
+        faces,
+        group.material,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Writes the files in a directory of their own and returns the error of loading the
+    // first one.
+    fn load_error(name: &str, files: &[(&str, &str)]) -> (PathBuf, usize, String) {
+        let dir = env::temp_dir().join(format!("raytracer-obj-{}", name));
+        fs::create_dir_all(&dir).expect("could not create the test directory");
+        for (file, src) in files {
+            fs::write(dir.join(file), src).expect("could not write the test file");
+        }
+        let path = dir.join(files[0].0);
+        let result = load(&path, Arc::new(Lambertian::new(Vec3::one())));
+        fs::remove_dir_all(&dir).expect("could not remove the test directory");
+        match result {
+            Ok(_) => panic!("the obj file should not load"),
+            Err(ObjError::Parse {
+                file,
+                line,
+                message,
+            }) => (
+                file.strip_prefix(&dir).unwrap().to_path_buf(),
+                line,
+                message,
+            ),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    const VERTICES: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    #[test]
+    fn face_with_two_vertices() {
+        let src = format!("{}f 1 2\n", VERTICES);
+        let (file, line, message) = load_error("two-vertices", &[("mesh.obj", &src)]);
+        assert_eq!((file.to_str(), line), (Some("mesh.obj"), 4));
+        assert_eq!(
+            message,
+            "malformed face, expected at least 3 vertices, found 2"
+        );
+    }
+
+    #[test]
+    fn index_zero() {
+        let src = format!("{}f 0 1 2\n", VERTICES);
+        let (file, line, message) = load_error("index-zero", &[("mesh.obj", &src)]);
+        assert_eq!((file.to_str(), line), (Some("mesh.obj"), 4));
+        assert!(message.contains("index"), "{}", message);
+    }
+
+    #[test]
+    fn negative_index_out_of_range() {
+        let src = format!("{}f -1 -2 -4\n", VERTICES);
+        let (file, line, message) = load_error("negative-index", &[("mesh.obj", &src)]);
+        assert_eq!((file.to_str(), line), (Some("mesh.obj"), 4));
+        assert!(message.contains("out of range"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_material() {
+        let src = format!("mtllib mesh.mtl\n{}usemtl blue\nf 1 2 3\n", VERTICES);
+        let mtl = "newmtl red\nKd 0.8 0.1 0.1\n";
+        let (file, line, message) =
+            load_error("unknown-material", &[("mesh.obj", &src), ("mesh.mtl", mtl)]);
+        assert_eq!((file.to_str(), line), (Some("mesh.obj"), 5));
+        assert_eq!(message, "unknown material 'blue'");
+    }
+
+    #[test]
+    fn missing_material_library() {
+        let src = format!("{}mtllib missing.mtl\nf 1 2 3\n", VERTICES);
+        let (file, line, message) = load_error("missing-library", &[("mesh.obj", &src)]);
+        assert_eq!((file.to_str(), line), (Some("mesh.obj"), 4));
+        assert!(message.contains("missing.mtl"), "{}", message);
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+// State of a render, reported each time a tile is done. Tiles are counted within the
+// current pass, samples over the whole render.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub pass: usize,
+    pub passes: usize,
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub samples_done: u64,
+    pub samples_total: u64,
+    pub samples_per_second: f64,
+    pub elapsed: Duration,
+    // Extrapolated from the speed so far.
+    pub remaining: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        if self.samples_total == 0 {
+            1.0
+        } else {
+            self.samples_done as f64 / self.samples_total as f64
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pass == self.passes && self.tiles_done == self.tiles_total
+    }
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+// Keeps a single status line up to date on the standard error.
+pub fn print(progress: &Progress) {
+    if progress.passes > 1 {
+        eprint!("\rPass {}/{}, tiles", progress.pass, progress.passes);
+    } else {
+        eprint!("\rTiles");
+    }
+    eprint!(
+        " {}/{} ({:.1}%), {:.2}M samples/s, elapsed {}, remaining {}   ",
+        progress.tiles_done,
+        progress.tiles_total,
+        100.0 * progress.fraction(),
+        progress.samples_per_second / 1e6,
+        format_duration(progress.elapsed),
+        format_duration(progress.remaining)
+    );
+    if progress.is_done() {
+        eprintln!();
+    }
+    io::stderr().flush().ok();
+}
@@ -1,19 +1,38 @@
+use crate::aov::Aov;
+use crate::background::Background;
+use crate::bvh::Bvh;
 use crate::camera::Camera;
-use crate::hitable::{Hitable, Sphere};
-use crate::list::List;
+use crate::film::{Film, PixelSamples, TileBuffer};
+use crate::hitable::{HitableArc, Ids, Sphere};
+use crate::image::{self, Image};
+use crate::integrator::{IntegratorArc, PathTracer, SceneContext};
+use crate::light::LightList;
 use crate::material::{Dielectric, Lambertian, Metal};
-use crate::ray::Ray;
+use crate::progress::{Progress, ProgressCallback};
+use crate::tile::{self, Tile, TileOrder};
+use crate::tonemap::ToneMapper;
 use crate::vec3::Vec3;
-use png::HasParameters;
 use rand::Rng;
 use std::f64;
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-type HitableArc = Arc<Hitable + Send + Sync>;
+// Samples per pixel before its noise is trusted to stop sampling it.
+const MIN_ADAPTIVE_SAMPLES: u32 = 16;
+
+// Work of one pass over the image.
+struct Pass<'a> {
+    samples: u32,
+    with_aovs: bool,
+    // Pixels to sample, in image order.
+    active: &'a [bool],
+    // Pixels are no longer sampled after this.
+    deadline: Option<Instant>,
+}
 
 #[derive(Builder, Clone)]
 #[builder(default)]
@@ -21,13 +40,42 @@ pub struct Raytracer {
     world: HitableArc,
     #[builder(default = "self.default_camera()")]
     camera: Camera,
+    background: Background,
+    integrator: IntegratorArc,
+    // Must hold every emissive object of the world when not empty.
+    lights: LightList,
     max_depth: u32,
+    // Paths deeper than this are randomly terminated depending on their throughput.
+    roulette_depth: u32,
     thread_nb: u32,
     res_x: u32,
     res_y: u32,
     antialiasing_samples: u32,
     #[builder(setter(into))]
     out_file: String,
+    // Exposure in stops and tone mapping operator of the images that are not floating point.
+    exposure: f64,
+    tone_mapper: ToneMapper,
+    // Bits per channel of PNG images, 8 or 16.
+    bit_depth: u8,
+    // Adds an alpha channel with the fraction of the camera rays hitting the world.
+    alpha: bool,
+    // Written next to `out_file`, with the name of the pass before the extension.
+    aovs: Vec<Aov>,
+    // Width and height of the tiles the threads pick one after the other.
+    tile_size: u32,
+    tile_order: TileOrder,
+    // Renders in passes doubling the samples per pixel, writing the image after each one.
+    progressive: bool,
+    // The render stops early once this time has passed, or once the relative noise of
+    // every pixel is under `target_noise`, with at most `antialiasing_samples` per pixel.
+    #[builder(setter(into))]
+    time_limit: Option<Duration>,
+    #[builder(setter(into))]
+    target_noise: Option<f64>,
+    // Called on the thread running the render each time a tile of a pass is done.
+    #[builder(setter(into))]
+    progress: Option<ProgressCallback>,
 }
 
 impl RaytracerBuilder {
@@ -58,12 +106,27 @@ impl Default for Raytracer {
         Raytracer {
             world: Self::build_sample_scene(),
             camera: Camera::default(),
+            background: Background::default(),
+            integrator: Arc::new(PathTracer),
+            lights: LightList::default(),
             max_depth: 50,
+            roulette_depth: 5,
             thread_nb: num_cpus::get() as u32,
             res_x,
             res_y,
             antialiasing_samples: 100,
             out_file: String::from("out.png"),
+            exposure: 0.0,
+            tone_mapper: ToneMapper::default(),
+            bit_depth: 8,
+            alpha: false,
+            aovs: Vec::new(),
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            progressive: false,
+            time_limit: None,
+            target_noise: None,
+            progress: None,
         }
     }
 }
@@ -87,92 +150,380 @@ impl Raytracer {
             Lambertian::new(Vec3::new(0.8, 0.8, 0.0)),
         );
 
-        let list: List<Arc<Hitable + Send + Sync>> = List::new()
-            .add(Arc::new(sphere1) as HitableArc)
-            .add(Arc::new(sphere2) as HitableArc)
-            .add(Arc::new(sphere3) as HitableArc)
-            .add(Arc::new(ground) as HitableArc);
+        // Every sphere has its own material.
+        let ids = |id| Ids {
+            object: id,
+            material: id,
+        };
+        let objects: Vec<HitableArc> = vec![
+            Arc::new(sphere1.with_ids(ids(1))),
+            Arc::new(sphere2.with_ids(ids(2))),
+            Arc::new(sphere3.with_ids(ids(3))),
+            Arc::new(ground.with_ids(ids(4))),
+        ];
 
-        Arc::new(list) as HitableArc
+        Arc::new(Bvh::new(objects)) as HitableArc
     }
 
-    fn color(&self, ray: &Ray, depth: u32) -> Vec3 {
-        match self.world.hit(ray, 0.001, f64::MAX) {
-            Some(hit_record) => {
-                if depth >= self.max_depth {
-                    return Vec3::zero();
-                }
-                match hit_record.material.scatter(ray, &hit_record) {
-                    Some(scatter_record) => {
-                        scatter_record.attenuation
-                            * self.color(&scatter_record.scattered, depth + 1)
-                    }
-                    None => Vec3::zero(),
-                }
+    // "out.png" becomes "out.depth.png" for the depth pass.
+    fn aov_path(&self, aov: Aov) -> PathBuf {
+        let path = Path::new(&self.out_file);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => {
+                format!("{}.{}.{}", stem, aov.name(), extension.to_string_lossy())
             }
-            None => {
-                let t = 0.5 * (ray.direction.normalized().y() + 1.0);
-                (1.0 - t) * Vec3::one() + t * Vec3::new(0.5, 0.7, 1.0)
+            None => format!("{}.{}", stem, aov.name()),
+        };
+        path.with_file_name(name)
+    }
+
+    // Samples through the pixel at column `i` and row `j` counted from the bottom of the
+    // image.
+    fn render_pixel<R: Rng>(
+        &self,
+        scene: &SceneContext,
+        i: u32,
+        j: u32,
+        count: u32,
+        random: &mut R,
+    ) -> PixelSamples {
+        let nx = self.res_x;
+        let ny = self.res_y;
+        let mut samples = PixelSamples {
+            color: Vec3::zero(),
+            luminance_squares: 0.0,
+            hits: 0.0,
+            count,
+        };
+        for _ in 0..count {
+            let u = (i as f64 + random.gen::<f64>()) / nx as f64;
+            let v = (j as f64 + random.gen::<f64>()) / ny as f64;
+            let ray = self.camera.get_ray(u, v);
+            let (col, hit) = self.integrator.color(scene, &ray);
+            if hit {
+                samples.hits += 1.0;
+            } else if self.alpha {
+                // The background is left out of images with an alpha channel, their colors
+                // are premultiplied by the coverage.
+                continue;
             }
+            samples.color += col;
+            samples.luminance_squares += col.luminance() * col.luminance();
         }
+        samples
     }
 
-    fn gamma_correct(v: &Vec3) -> Vec3 {
-        Vec3::new(v.r().sqrt(), v.g().sqrt(), v.b().sqrt())
+    // The passes use a single ray through the center of the pixel, so that identifiers
+    // are not blended together.
+    fn render_aovs(&self, i: u32, j: u32) -> Vec<Vec3> {
+        let u = (i as f64 + 0.5) / self.res_x as f64;
+        let v = (j as f64 + 0.5) / self.res_y as f64;
+        let ray = self.camera.get_ray(u, v);
+        let hit_record = self.world.hit(&ray, 0.001, f64::MAX);
+        self.aovs
+            .iter()
+            .map(|aov| aov.value(&ray, hit_record.as_ref()))
+            .collect()
     }
 
-    pub fn run(&self) {
+    // Samples per pixel of each pass. Progressive renders, and the ones that may stop
+    // early, double the samples of the image with each pass, 1, 2, 4… up to
+    // `antialiasing_samples`.
+    fn passes(&self) -> Vec<u32> {
+        let total = self.antialiasing_samples;
+        let single_pass =
+            !self.progressive && self.time_limit.is_none() && self.target_noise.is_none();
+        // A render without samples still has a pass, writing a black image.
+        if single_pass || total == 0 {
+            return vec![total];
+        }
+        let mut passes = Vec::new();
+        let mut done = 0;
+        while done < total {
+            let samples = done.max(1).min(total - done);
+            passes.push(samples);
+            done += samples;
+        }
+        passes
+    }
+
+    // Renders the samples of a pass in every active pixel, handing each tile to `on_tile`
+    // as soon as it is done.
+    fn render_pass<F: FnMut(TileBuffer)>(
+        &self,
+        scene: &SceneContext,
+        tiles: &[Tile],
+        pass: &Pass,
+        mut on_tile: F,
+    ) {
         let nx = self.res_x;
         let ny = self.res_y;
-        let thread_nb = self.thread_nb;
-
-        println!("Running with {} threads", thread_nb);
-
-        let path = Path::new(&self.out_file);
-        let file = File::create(path).expect("Could not create file");
-        let w = BufWriter::new(file);
-        let mut encoder = png::Encoder::new(w, nx, ny);
-        encoder.set(png::ColorType::RGB);
-        let mut writer = encoder.write_header().expect("Could not write png header");
-
-        let data = Arc::new(Mutex::new(vec![0; (nx * ny * 3) as usize]));
+        let aov_count = if pass.with_aovs { self.aovs.len() } else { 0 };
+        let next_tile = AtomicUsize::new(0);
 
         crossbeam::scope(|scope| {
-            for thread_ind in 0..thread_nb {
-                let data = data.clone();
-
-                let start_y = ny / thread_nb * thread_ind;
-                let end_y = ny / thread_nb * (thread_ind + 1);
+            // Each tile is rendered in its own buffer, which is sent back to be merged
+            // into the film, so that the threads never wait for each other.
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..self.thread_nb {
+                let sender = sender.clone();
+                let next_tile = &next_tile;
 
                 scope.spawn(move |_| {
                     let mut random = rand::thread_rng();
-                    let mut ind =
-                        ((thread_nb - thread_ind - 1) * (nx * ny * 3 / thread_nb)) as usize;
-                    for j in (start_y..end_y).rev() {
-                        for i in 0..nx {
-                            let mut col = Vec3::zero();
-                            for _ in 0..self.antialiasing_samples {
-                                let u = (i as f64 + random.gen::<f64>()) / nx as f64;
-                                let v = (j as f64 + random.gen::<f64>()) / ny as f64;
-                                let ray = self.camera.get_ray(u, v);
-                                col += self.color(&ray, 0);
+                    // Threads pick the next tile as soon as they are done with the previous
+                    // one, so that none of them stays idle while there is work left.
+                    let mut timed_out = false;
+                    while let Some(&tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        if timed_out {
+                            break;
+                        }
+                        let mut buffer = TileBuffer::new(tile, aov_count);
+                        // Late passes put many samples in each pixel, so the time limit is
+                        // checked before each of them and the tile is merged as it is.
+                        'tile: for y in tile.y..tile.y + tile.height {
+                            for x in tile.x..tile.x + tile.width {
+                                if pass.deadline.is_some_and(|d| Instant::now() >= d) {
+                                    timed_out = true;
+                                    break 'tile;
+                                }
+                                if !pass.active[(y * nx + x) as usize] {
+                                    continue;
+                                }
+                                let samples = self.render_pixel(
+                                    scene,
+                                    x,
+                                    ny - 1 - y,
+                                    pass.samples,
+                                    &mut random,
+                                );
+                                buffer.set(x, y, &samples);
+                                if aov_count > 0 {
+                                    buffer.set_aovs(x, y, self.render_aovs(x, ny - 1 - y));
+                                }
                             }
-                            col = Self::gamma_correct(&(col / self.antialiasing_samples as f64))
-                                * 255.99;
-                            let mut data_u = data.lock().expect("Error while locking image buffer");
-                            data_u[ind] = col.r() as u8;
-                            data_u[ind + 1] = col.g() as u8;
-                            data_u[ind + 2] = col.b() as u8;
-                            ind += 3;
                         }
+                        sender.send(buffer).expect("Error while sending a tile");
                     }
                 });
             }
+            drop(sender);
+            for buffer in receiver {
+                on_tile(buffer);
+            }
         })
         .expect("Error while joining thread");
+    }
+
+    // Pixels needing more samples, all of them unless a target noise is set.
+    fn active_pixels(&self, film: &Film) -> Vec<bool> {
+        match self.target_noise {
+            Some(target) => film
+                .noise()
+                .iter()
+                .zip(film.samples())
+                .map(|(&noise, &samples)| samples < MIN_ADAPTIVE_SAMPLES || noise > target)
+                .collect(),
+            None => vec![true; film.samples().len()],
+        }
+    }
 
-        writer
-            .write_image_data(&data.lock().unwrap())
-            .expect("Error while writing png");
+    pub fn run(&self) {
+        let nx = self.res_x;
+        let ny = self.res_y;
+
+        println!("Running with {} threads", self.thread_nb);
+
+        let scene = SceneContext {
+            world: &*self.world,
+            lights: &self.lights,
+            background: &self.background,
+            max_depth: self.max_depth,
+            roulette_depth: self.roulette_depth,
+        };
+
+        let tiles = tile::tiles(nx, ny, self.tile_size, self.tile_order);
+        let passes = self.passes();
+        let mut film = Film::new(nx, ny, self.aovs.len());
+        let start = Instant::now();
+        let deadline = self.time_limit.map(|limit| start + limit);
+        let mut samples_total = 0;
+        let mut samples_done = 0;
+        let mut passes_done = 0;
+        let mut stopped = false;
+
+        for (pass, &samples) in passes.iter().enumerate() {
+            let active = self.active_pixels(&film);
+            // Pixels that reached the target noise are done, the other ones may still get
+            // all their samples.
+            samples_total = samples_done
+                + active
+                    .iter()
+                    .zip(film.samples())
+                    .filter(|(&active, _)| active)
+                    .map(|(_, &done)| u64::from(self.antialiasing_samples.saturating_sub(done)))
+                    .sum::<u64>();
+            if pass > 0
+                && (deadline.is_some_and(|d| Instant::now() >= d) || !active.contains(&true))
+            {
+                stopped = true;
+                break;
+            }
+            // The first pass is always complete, so that every pixel has a sample.
+            let settings = Pass {
+                samples,
+                with_aovs: pass == 0,
+                active: &active,
+                deadline: if pass == 0 { None } else { deadline },
+            };
+            let mut tiles_done = 0;
+            self.render_pass(&scene, &tiles, &settings, |buffer| {
+                film.merge(&buffer);
+                tiles_done += 1;
+                samples_done += buffer.sample_count();
+                if let Some(ref callback) = self.progress {
+                    let elapsed = start.elapsed();
+                    let seconds = elapsed.as_secs_f64();
+                    // Unknown until some samples are done, as when there are none to do.
+                    let mut remaining = if samples_done > 0 {
+                        elapsed.mul_f64((samples_total - samples_done) as f64 / samples_done as f64)
+                    } else {
+                        Duration::from_secs(0)
+                    };
+                    if let Some(deadline) = deadline {
+                        remaining =
+                            remaining.min(deadline.saturating_duration_since(Instant::now()));
+                    }
+                    callback(&Progress {
+                        pass: pass + 1,
+                        passes: passes.len(),
+                        tiles_done,
+                        tiles_total: tiles.len(),
+                        samples_done,
+                        samples_total,
+                        samples_per_second: samples_done as f64 / seconds.max(f64::EPSILON),
+                        elapsed,
+                        remaining,
+                    });
+                }
+            });
+            passes_done += 1;
+            // Every pass overwrites the image, which can be looked at while the next one
+            // is rendered.
+            if self.progressive {
+                self.save(&film)
+                    .expect("Error while writing the output image");
+            }
+        }
+
+        // The time limit can also end the last pass.
+        stopped |= samples_done < samples_total;
+        if stopped {
+            let elapsed = start.elapsed();
+            if let Some(ref callback) = self.progress {
+                // The render is done with fewer samples than planned.
+                callback(&Progress {
+                    pass: passes_done,
+                    passes: passes_done,
+                    tiles_done: tiles.len(),
+                    tiles_total: tiles.len(),
+                    samples_done,
+                    samples_total: samples_done,
+                    samples_per_second: samples_done as f64
+                        / elapsed.as_secs_f64().max(f64::EPSILON),
+                    elapsed,
+                    remaining: Duration::from_secs(0),
+                });
+            }
+            println!(
+                "Stopped with {:.1} samples per pixel on average",
+                samples_done as f64 / (u64::from(nx) * u64::from(ny)) as f64
+            );
+        }
+        // Progressive renders were written after their last pass.
+        if !self.progressive {
+            self.save(&film)
+                .expect("Error while writing the output image");
+        }
+    }
+
+    // The format depends on the extension of the output file. OpenEXR images hold the
+    // linear radiance and the passes as extra channels, Radiance HDR images the linear
+    // radiance, and PNG, PPM, TGA and BMP images the tone mapped colors. Except for
+    // OpenEXR, the passes are written to their own files in the same format.
+    fn save(&self, film: &Film) -> io::Result<()> {
+        let pixels = film.pixels();
+        let coverage = if self.alpha {
+            Some(film.coverage())
+        } else {
+            None
+        };
+        let alpha = coverage.as_deref();
+        let aovs: Vec<(Aov, &Vec<Vec3>)> = self.aovs.iter().cloned().zip(&film.aovs).collect();
+        let width = self.res_x as usize;
+        let height = self.res_y as usize;
+        let extension = Path::new(&self.out_file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        if extension == "exr" {
+            let mut channels = Vec::new();
+            for (index, name) in ["R", "G", "B"].iter().enumerate() {
+                let values = pixels.iter().map(|p| p[index] as f32).collect();
+                channels.push((name.to_string(), values));
+            }
+            if let Some(alpha) = alpha {
+                let values = alpha.iter().map(|&a| a as f32).collect();
+                channels.push((String::from("A"), values));
+            }
+            for (aov, values) in &aovs {
+                for (index, name) in aov.channels().iter().enumerate() {
+                    let values = values.iter().map(|v| v[index] as f32).collect();
+                    channels.push((name.to_string(), values));
+                }
+            }
+            return image::save_exr(&self.out_file, width, height, &mut channels);
+        }
+
+        let save = |path: &Path, pixels: Vec<Vec3>, alpha: Option<&[f64]>| {
+            let image = Image {
+                width,
+                height,
+                pixels,
+            };
+            match extension.as_str() {
+                "hdr" => image.save_hdr(path),
+                "ppm" => image.save_ppm(path),
+                "tga" => image.save_tga(path, alpha),
+                "bmp" => image.save_bmp(path),
+                _ => image.save_png(path, self.bit_depth == 16, alpha),
+            }
+        };
+        // PNG and TGA images hold straight colors, the other formats have no alpha and
+        // keep the image over black.
+        let pixels = match alpha {
+            Some(alpha) if extension != "hdr" && extension != "ppm" && extension != "bmp" => pixels
+                .iter()
+                .zip(alpha)
+                .map(|(&p, &a)| if a > 0.0 { p / a } else { p })
+                .collect(),
+            _ => pixels,
+        };
+        let pixels = if extension == "hdr" {
+            pixels
+        } else {
+            pixels
+                .iter()
+                .map(|p| self.tone_mapper.apply(p, self.exposure))
+                .collect()
+        };
+        save(Path::new(&self.out_file), pixels, alpha)?;
+        for (aov, values) in &aovs {
+            save(&self.aov_path(*aov), aov.display(values), None)?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::environment::EnvironmentMap;
+use crate::hitable::{HitableArc, Ids, Sphere};
+use crate::image::Image;
+use crate::light::LightList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, MaterialArc, Metal};
+use crate::obj;
+use crate::texture::{
+    CheckerTexture, ConstantTexture, Filter, ImageTexture, NoisePattern, NoiseTexture, TextureArc,
+    Wrap,
+};
+use crate::triangle::{Mesh, MeshFace, Triangle};
+use crate::vec3::Vec3;
+
+#[derive(Debug)]
+pub struct ParseError {
+    line: usize,
+    col: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new<S: Into<String>>(token: &Token, message: S) -> ParseError {
+        ParseError {
+            line: token.line,
+            col: token.col,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "could not read scene file: {}", e),
+            SceneError::Parse(e) => write!(f, "invalid scene file at {}", e),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<ParseError> for SceneError {
+    fn from(e: ParseError) -> Self {
+        SceneError::Parse(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    col: usize,
+}
+
+fn tokenize_line(line: &str, line_nb: usize) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    for (i, c) in line.chars().enumerate() {
+        let col = i + 1;
+        if c == '#' {
+            break;
+        }
+        if c.is_whitespace() || c == '{' || c == '}' {
+            if let Some(token) = current.take() {
+                tokens.push(token);
+            }
+            if !c.is_whitespace() {
+                tokens.push(Token {
+                    text: c.to_string(),
+                    line: line_nb,
+                    col,
+                });
+            }
+        } else {
+            match current {
+                Some(ref mut token) => token.text.push(c),
+                None => {
+                    current = Some(Token {
+                        text: c.to_string(),
+                        line: line_nb,
+                        col,
+                    })
+                }
+            }
+        }
+    }
+    if let Some(token) = current {
+        tokens.push(token);
+    }
+    tokens
+}
+
+struct Property {
+    key: Token,
+    values: Vec<Token>,
+}
+
+impl Property {
+    fn expect_count(&self, count: usize) -> Result<(), ParseError> {
+        if self.values.len() != count {
+            return Err(ParseError::new(
+                &self.key,
+                format!(
+                    "'{}' expects {} value(s), found {}",
+                    self.key.text,
+                    count,
+                    self.values.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn number_at(&self, i: usize) -> Result<f64, ParseError> {
+        let token = &self.values[i];
+        token.text.parse().map_err(|_| {
+            ParseError::new(token, format!("expected a number, found '{}'", token.text))
+        })
+    }
+
+    fn number(&self) -> Result<f64, ParseError> {
+        self.expect_count(1)?;
+        self.number_at(0)
+    }
+
+    fn vec3(&self) -> Result<Vec3, ParseError> {
+        self.expect_count(3)?;
+        Ok(Vec3::new(
+            self.number_at(0)?,
+            self.number_at(1)?,
+            self.number_at(2)?,
+        ))
+    }
+
+    fn index_at(&self, i: usize, len: usize) -> Result<usize, ParseError> {
+        let token = &self.values[i];
+        match token.text.parse::<usize>() {
+            Ok(index) if index < len => Ok(index),
+            Ok(index) => Err(ParseError::new(
+                token,
+                format!("index {} is out of range, only {} are defined", index, len),
+            )),
+            Err(_) => Err(ParseError::new(
+                token,
+                format!("expected an index, found '{}'", token.text),
+            )),
+        }
+    }
+
+    fn word(&self) -> Result<&Token, ParseError> {
+        self.expect_count(1)?;
+        Ok(&self.values[0])
+    }
+
+    fn boolean(&self) -> Result<bool, ParseError> {
+        let token = self.word()?;
+        match token.text.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(ParseError::new(
+                token,
+                format!("expected 'true' or 'false', found '{}'", token.text),
+            )),
+        }
+    }
+}
+
+struct Block {
+    header: Vec<Token>,
+    properties: Vec<Property>,
+}
+
+impl Block {
+    fn kind(&self) -> &Token {
+        &self.header[0]
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), ParseError> {
+        self.check_repeated_keys(allowed, &[])
+    }
+
+    fn check_repeated_keys(&self, allowed: &[&str], repeated: &[&str]) -> Result<(), ParseError> {
+        let mut seen = HashMap::new();
+        for property in &self.properties {
+            let key = property.key.text.as_str();
+            if repeated.contains(&key) {
+                continue;
+            }
+            if !allowed.contains(&key) {
+                return Err(ParseError::new(
+                    &property.key,
+                    format!("unknown property '{}' in '{}'", key, self.kind().text),
+                ));
+            }
+            if seen.insert(key, ()).is_some() {
+                return Err(ParseError::new(
+                    &property.key,
+                    format!("duplicate property '{}'", key),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.properties.iter().filter(move |p| p.key.text == key)
+    }
+
+    fn get(&self, key: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.key.text == key)
+    }
+
+    fn require(&self, key: &str) -> Result<&Property, ParseError> {
+        self.get(key).ok_or_else(|| {
+            ParseError::new(
+                self.kind(),
+                format!("missing property '{}' in '{}'", key, self.kind().text),
+            )
+        })
+    }
+
+    fn number(&self, key: &str) -> Result<f64, ParseError> {
+        self.require(key)?.number()
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, ParseError> {
+        self.get(key).map_or(Ok(default), Property::number)
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vec3, ParseError> {
+        self.require(key)?.vec3()
+    }
+
+    fn vec3_or(&self, key: &str, default: Vec3) -> Result<Vec3, ParseError> {
+        self.get(key).map_or(Ok(default), Property::vec3)
+    }
+
+    fn word(&self, key: &str) -> Result<&Token, ParseError> {
+        self.require(key)?.word()
+    }
+
+    fn expect_args(&self, count: usize, usage: &str) -> Result<(), ParseError> {
+        if self.header.len() != count + 1 {
+            return Err(ParseError::new(
+                self.kind(),
+                format!("expected '{} {{'", usage),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_blocks(src: &str) -> Result<Vec<Block>, ParseError> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        let mut tokens = tokenize_line(line, i + 1);
+        if tokens.is_empty() {
+            continue;
+        }
+        match current.take() {
+            None => {
+                let open = tokens.pop().unwrap();
+                if open.text != "{" {
+                    return Err(ParseError::new(&open, "expected '{' at end of line"));
+                }
+                if tokens.is_empty() {
+                    return Err(ParseError::new(&open, "expected a block name before '{'"));
+                }
+                if let Some(token) = tokens.iter().find(|t| t.text == "{" || t.text == "}") {
+                    return Err(ParseError::new(
+                        token,
+                        format!("unexpected '{}'", token.text),
+                    ));
+                }
+                current = Some(Block {
+                    header: tokens,
+                    properties: Vec::new(),
+                });
+            }
+            Some(mut block) => {
+                if let Some(token) = tokens.iter().find(|t| t.text == "{") {
+                    return Err(ParseError::new(
+                        token,
+                        "unexpected '{', blocks cannot be nested",
+                    ));
+                }
+                if let Some(pos) = tokens.iter().position(|t| t.text == "}") {
+                    if pos != 0 || tokens.len() != 1 {
+                        return Err(ParseError::new(&tokens[pos], "'}' must be on its own line"));
+                    }
+                    blocks.push(block);
+                } else {
+                    let key = tokens.remove(0);
+                    block.properties.push(Property {
+                        key,
+                        values: tokens,
+                    });
+                    current = Some(block);
+                }
+            }
+        }
+    }
+
+    if let Some(block) = current {
+        return Err(ParseError::new(
+            block.kind(),
+            format!("unclosed '{}' block", block.kind().text),
+        ));
+    }
+
+    Ok(blocks)
+}
+
+struct CameraDesc {
+    look_from: Vec3,
+    look_at: Vec3,
+    vup: Vec3,
+    vfov: f64,
+    aperture: f64,
+    focus_dist: f64,
+}
+
+impl CameraDesc {
+    fn parse(block: &Block) -> Result<CameraDesc, ParseError> {
+        block.expect_args(0, "camera")?;
+        block.check_keys(&[
+            "look_from",
+            "look_at",
+            "vup",
+            "vfov",
+            "aperture",
+            "focus_dist",
+        ])?;
+        let look_from = block.vec3("look_from")?;
+        let look_at = block.vec3("look_at")?;
+        Ok(CameraDesc {
+            look_from,
+            look_at,
+            vup: block.vec3_or("vup", Vec3::new(0.0, 1.0, 0.0))?,
+            vfov: block.number_or("vfov", 20.0)?,
+            aperture: block.number_or("aperture", 0.0)?,
+            focus_dist: block.number_or("focus_dist", (look_from - look_at).length())?,
+        })
+    }
+}
+
+fn lookup<T: Clone>(items: &HashMap<String, T>, name: &Token, kind: &str) -> Result<T, ParseError> {
+    items
+        .get(&name.text)
+        .cloned()
+        .ok_or_else(|| ParseError::new(name, format!("unknown {} '{}'", kind, name.text)))
+}
+
+// A texture property is either a constant color or the name of a texture.
+fn parse_texture_property(
+    property: &Property,
+    textures: &HashMap<String, TextureArc>,
+) -> Result<TextureArc, ParseError> {
+    if property.values.len() == 1 {
+        lookup(textures, &property.values[0], "texture")
+    } else {
+        Ok(Arc::new(ConstantTexture::new(property.vec3()?)))
+    }
+}
+
+fn parse_texture(
+    block: &Block,
+    textures: &HashMap<String, TextureArc>,
+    dir: &Path,
+) -> Result<TextureArc, ParseError> {
+    let kind = &block.header[2];
+    let texture: TextureArc = match kind.text.as_str() {
+        "constant" => {
+            block.check_keys(&["color"])?;
+            Arc::new(ConstantTexture::new(block.vec3("color")?))
+        }
+        "checker" => {
+            block.check_keys(&["odd", "even", "scale"])?;
+            Arc::new(CheckerTexture::new(
+                parse_texture_property(block.require("odd")?, textures)?,
+                parse_texture_property(block.require("even")?, textures)?,
+                block.number_or("scale", 1.0)?,
+            ))
+        }
+        "noise" | "turbulence" | "marble" | "wood" => {
+            block.check_keys(&["low", "high", "scale", "seed"])?;
+            let pattern = match kind.text.as_str() {
+                "noise" => NoisePattern::Noise,
+                "turbulence" => NoisePattern::Turbulence,
+                "marble" => NoisePattern::Marble,
+                _ => NoisePattern::Wood,
+            };
+            let low = match block.get("low") {
+                Some(property) => parse_texture_property(property, textures)?,
+                None => Arc::new(ConstantTexture::new(Vec3::zero())),
+            };
+            let high = match block.get("high") {
+                Some(property) => parse_texture_property(property, textures)?,
+                None => Arc::new(ConstantTexture::new(Vec3::one())),
+            };
+            Arc::new(NoiseTexture::new(
+                pattern,
+                block.number_or("scale", 1.0)?,
+                low,
+                high,
+                block.number_or("seed", 0.0)? as u64,
+            ))
+        }
+        "image" => {
+            block.check_keys(&["file", "wrap", "filter", "srgb"])?;
+            let wrap = match block.get("wrap") {
+                None => Wrap::Repeat,
+                Some(property) => {
+                    let token = property.word()?;
+                    match token.text.as_str() {
+                        "repeat" => Wrap::Repeat,
+                        "clamp" => Wrap::Clamp,
+                        _ => return Err(ParseError::new(token, "expected 'repeat' or 'clamp'")),
+                    }
+                }
+            };
+            let filter = match block.get("filter") {
+                None => Filter::Bilinear,
+                Some(property) => {
+                    let token = property.word()?;
+                    match token.text.as_str() {
+                        "bilinear" => Filter::Bilinear,
+                        "nearest" => Filter::Nearest,
+                        _ => {
+                            return Err(ParseError::new(token, "expected 'bilinear' or 'nearest'"))
+                        }
+                    }
+                }
+            };
+            let srgb = match block.get("srgb") {
+                None => true,
+                Some(property) => property.boolean()?,
+            };
+            let file = block.word("file")?;
+            let image = Image::load(dir.join(&file.text), srgb)
+                .map_err(|e| ParseError::new(file, format!("could not load image, {}", e)))?;
+            Arc::new(ImageTexture::new(Arc::new(image), wrap, filter))
+        }
+        _ => {
+            return Err(ParseError::new(
+                kind,
+                format!("unknown texture type '{}'", kind.text),
+            ))
+        }
+    };
+    Ok(texture)
+}
+
+fn parse_material(
+    block: &Block,
+    textures: &HashMap<String, TextureArc>,
+) -> Result<MaterialArc, ParseError> {
+    let kind = &block.header[2];
+    let material: MaterialArc = match kind.text.as_str() {
+        "lambertian" => {
+            block.check_keys(&["albedo"])?;
+            Arc::new(Lambertian::textured(parse_texture_property(
+                block.require("albedo")?,
+                textures,
+            )?))
+        }
+        "metal" => {
+            block.check_keys(&["albedo", "fuzz"])?;
+            Arc::new(Metal::textured(
+                parse_texture_property(block.require("albedo")?, textures)?,
+                block.number_or("fuzz", 0.0)?,
+            ))
+        }
+        "dielectric" => {
+            block.check_keys(&["ref_ind"])?;
+            Arc::new(Dielectric::new(block.number("ref_ind")?))
+        }
+        "diffuse_light" => {
+            block.check_keys(&["emit"])?;
+            Arc::new(DiffuseLight::new(block.vec3("emit")?))
+        }
+        _ => {
+            return Err(ParseError::new(
+                kind,
+                format!("unknown material type '{}'", kind.text),
+            ))
+        }
+    };
+    Ok(material)
+}
+
+fn parse_background(block: &Block, dir: &Path) -> Result<Background, ParseError> {
+    let kind = &block.header[1];
+    let background = match kind.text.as_str() {
+        "solid" => {
+            block.check_keys(&["color"])?;
+            Background::Solid(block.vec3("color")?)
+        }
+        "gradient" => {
+            block.check_keys(&["bottom", "top"])?;
+            Background::Gradient {
+                bottom: block.vec3("bottom")?,
+                top: block.vec3("top")?,
+            }
+        }
+        "environment" => {
+            block.check_keys(&["file", "rotation", "intensity"])?;
+            let file = block.word("file")?;
+            let map = EnvironmentMap::load(
+                dir.join(&file.text),
+                block.number_or("rotation", 0.0)?,
+                block.number_or("intensity", 1.0)?,
+            )
+            .map_err(|e| ParseError::new(file, format!("could not load environment map, {}", e)))?;
+            Background::Environment(Arc::new(map))
+        }
+        _ => {
+            return Err(ParseError::new(
+                kind,
+                format!("unknown background type '{}'", kind.text),
+            ))
+        }
+    };
+    Ok(background)
+}
+
+// Numbers the materials in the order in which they are first used.
+#[derive(Default)]
+struct MaterialIds {
+    ids: HashMap<*const (), u32>,
+}
+
+impl MaterialIds {
+    fn ids(&mut self, object: u32, material: &MaterialArc) -> Ids {
+        let next = self.ids.len() as u32 + 1;
+        let material = *self
+            .ids
+            .entry(Arc::as_ptr(material) as *const ())
+            .or_insert(next);
+        Ids { object, material }
+    }
+}
+
+pub struct Scene {
+    pub world: HitableArc,
+    pub background: Option<Background>,
+    pub lights: LightList,
+    camera: Option<CameraDesc>,
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(Self::parse(&src, dir)?)
+    }
+
+    // Files referenced by the scene are looked up relative to `dir`.
+    pub fn parse(src: &str, dir: &Path) -> Result<Scene, ParseError> {
+        let mut camera = None;
+        let mut background = None;
+        let mut textures: HashMap<String, TextureArc> = HashMap::new();
+        let mut materials: HashMap<String, MaterialArc> = HashMap::new();
+        let mut objects: Vec<HitableArc> = Vec::new();
+        // Objects are numbered in the order of the blocks.
+        let mut object_count = 0;
+        let mut material_ids = MaterialIds::default();
+
+        for block in parse_blocks(src)? {
+            let kind = block.kind();
+            match kind.text.as_str() {
+                "camera" => {
+                    if camera.is_some() {
+                        return Err(ParseError::new(kind, "camera is defined more than once"));
+                    }
+                    camera = Some(CameraDesc::parse(&block)?);
+                }
+                "background" => {
+                    if background.is_some() {
+                        return Err(ParseError::new(
+                            kind,
+                            "background is defined more than once",
+                        ));
+                    }
+                    block.expect_args(1, "background <type>")?;
+                    background = Some(parse_background(&block, dir)?);
+                }
+                "material" => {
+                    block.expect_args(2, "material <name> <type>")?;
+                    let name = &block.header[1];
+                    if materials.contains_key(&name.text) {
+                        return Err(ParseError::new(
+                            name,
+                            format!("material '{}' is defined more than once", name.text),
+                        ));
+                    }
+                    materials.insert(name.text.clone(), parse_material(&block, &textures)?);
+                }
+                "texture" => {
+                    block.expect_args(2, "texture <name> <type>")?;
+                    let name = &block.header[1];
+                    if textures.contains_key(&name.text) {
+                        return Err(ParseError::new(
+                            name,
+                            format!("texture '{}' is defined more than once", name.text),
+                        ));
+                    }
+                    let texture = parse_texture(&block, &textures, dir)?;
+                    textures.insert(name.text.clone(), texture);
+                }
+                "sphere" => {
+                    block.expect_args(0, "sphere")?;
+                    block.check_keys(&["center", "radius", "material"])?;
+                    let material = lookup(&materials, block.word("material")?, "material")?;
+                    object_count += 1;
+                    let ids = material_ids.ids(object_count, &material);
+                    let sphere =
+                        Sphere::new(block.vec3("center")?, block.number("radius")?, material)
+                            .with_ids(ids);
+                    objects.push(Arc::new(sphere));
+                }
+                "triangle" => {
+                    block.expect_args(0, "triangle")?;
+                    block.check_keys(&["v0", "v1", "v2", "material"])?;
+                    let material = lookup(&materials, block.word("material")?, "material")?;
+                    object_count += 1;
+                    let ids = material_ids.ids(object_count, &material);
+                    let triangle = Triangle::new(
+                        block.vec3("v0")?,
+                        block.vec3("v1")?,
+                        block.vec3("v2")?,
+                        material,
+                    )
+                    .with_ids(ids);
+                    objects.push(Arc::new(triangle));
+                }
+                "mesh" => {
+                    block.expect_args(0, "mesh")?;
+                    let material = lookup(&materials, block.word("material")?, "material")?;
+                    object_count += 1;
+                    let ids = material_ids.ids(object_count, &material);
+                    let mesh = Self::parse_mesh(&block, material)?.with_ids(ids);
+                    objects.extend(Arc::new(mesh).triangles());
+                }
+                "model" => {
+                    block.expect_args(0, "model")?;
+                    block.check_keys(&["file", "material"])?;
+                    let default_material = match block.get("material") {
+                        Some(property) => lookup(&materials, property.word()?, "material")?,
+                        None => Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.8))),
+                    };
+                    let file = block.word("file")?;
+                    let meshes =
+                        obj::load(dir.join(&file.text), default_material).map_err(|e| {
+                            ParseError::new(file, format!("could not load model, {}", e))
+                        })?;
+                    object_count += 1;
+                    for mesh in meshes {
+                        let ids = material_ids.ids(object_count, mesh.material());
+                        objects.extend(Arc::new(mesh.with_ids(ids)).triangles());
+                    }
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        kind,
+                        format!("unknown block '{}'", kind.text),
+                    ))
+                }
+            }
+        }
+
+        let lights = objects
+            .iter()
+            .filter(|object| object.is_emissive())
+            .cloned()
+            .collect();
+        Ok(Scene {
+            lights: LightList::new(lights),
+            world: Arc::new(Bvh::new(objects)),
+            background,
+            camera,
+        })
+    }
+
+    fn parse_mesh(block: &Block, material: MaterialArc) -> Result<Mesh<MaterialArc>, ParseError> {
+        block.check_repeated_keys(&["material"], &["vertex", "normal", "uv", "face"])?;
+        let vertices = block
+            .all("vertex")
+            .map(Property::vec3)
+            .collect::<Result<Vec<_>, _>>()?;
+        let normals = block
+            .all("normal")
+            .map(Property::vec3)
+            .collect::<Result<Vec<_>, _>>()?;
+        let uvs = block
+            .all("uv")
+            .map(|p| {
+                p.expect_count(2)?;
+                Ok((p.number_at(0)?, p.number_at(1)?))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, len) in &[("normal", normals.len()), ("uv", uvs.len())] {
+            if *len != 0 && *len != vertices.len() {
+                return Err(ParseError::new(
+                    block.kind(),
+                    format!("expected one '{}' per vertex", key),
+                ));
+            }
+        }
+
+        let faces = block
+            .all("face")
+            .map(|p| {
+                p.expect_count(3)?;
+                let indices = [
+                    p.index_at(0, vertices.len())?,
+                    p.index_at(1, vertices.len())?,
+                    p.index_at(2, vertices.len())?,
+                ];
+                Ok(MeshFace {
+                    vertices: indices,
+                    normals: if normals.is_empty() {
+                        None
+                    } else {
+                        Some(indices)
+                    },
+                    uvs: if uvs.is_empty() { None } else { Some(indices) },
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Mesh::new(vertices, normals, uvs, faces, material))
+    }
+
+    pub fn camera(&self, aspect: f64) -> Option<Camera> {
+        self.camera.as_ref().map(|desc| {
+            Camera::new(
+                desc.look_from,
+                desc.look_at,
+                desc.vup,
+                desc.vfov,
+                desc.aperture,
+                desc.focus_dist,
+                aspect,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(src: &str) -> ParseError {
+        match Scene::parse(src, Path::new("")) {
+            Ok(_) => panic!("the scene should not parse"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn unknown_property() {
+        let src = "material red lambertian {\n    albedo 0.8 0.3 0.3\n    shine 2\n}\n";
+        let error = parse_error(src);
+        assert_eq!((error.line, error.col), (3, 5));
+        assert!(error.message.contains("shine"), "{}", error);
+    }
+
+    #[test]
+    fn missing_closing_brace() {
+        let src = "sphere {\n    center 0 0 -1\n    radius 0.5\n";
+        let error = parse_error(src);
+        assert_eq!((error.line, error.col), (1, 1));
+        assert_eq!(error.to_string(), "1:1: unclosed 'sphere' block");
+    }
+
+    #[test]
+    fn bad_number() {
+        let src = "material red metal {\n    albedo 0.8 0.3 0.3\n    fuzz 0.1x\n}\n";
+        let error = parse_error(src);
+        assert_eq!((error.line, error.col), (3, 10));
+        assert_eq!(error.to_string(), "3:10: expected a number, found '0.1x'");
+    }
+}
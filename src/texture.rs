@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::perlin::Perlin;
+use crate::vec3::Vec3;
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+}
+
+pub type TextureArc = Arc<dyn Texture + Send + Sync>;
+
+pub struct ConstantTexture {
+    color: Vec3,
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vec3) -> ConstantTexture {
+        ConstantTexture { color }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _u: f64, _v: f64, _p: &Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+pub struct CheckerTexture {
+    odd: TextureArc,
+    even: TextureArc,
+    scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(odd: TextureArc, even: TextureArc, scale: f64) -> CheckerTexture {
+        CheckerTexture { odd, even, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let cell = (self.scale * p.x()).floor()
+            + (self.scale * p.y()).floor()
+            + (self.scale * p.z()).floor();
+        if cell.rem_euclid(2.0) < 1.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: Wrap,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>, wrap: Wrap, filter: Filter) -> ImageTexture {
+        ImageTexture {
+            image,
+            wrap,
+            filter,
+        }
+    }
+
+    fn wrap(&self, i: i64, size: usize) -> usize {
+        match self.wrap {
+            Wrap::Repeat => i.rem_euclid(size as i64) as usize,
+            Wrap::Clamp => i.clamp(0, size as i64 - 1) as usize,
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        self.image.pixel(
+            self.wrap(x, self.image.width),
+            self.wrap(y, self.image.height),
+        )
+    }
+}
+
+impl Texture for ImageTexture {
+    // The bottom left corner of the image is at (0, 0) in texture coordinates.
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Vec3 {
+        let x = u * self.image.width as f64;
+        let y = (1.0 - v) * self.image.height as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let x0 = x0 as i64;
+                let y0 = y0 as i64;
+                (1.0 - fy) * ((1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0))
+                    + fy * ((1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    Noise,
+    Turbulence,
+    Marble,
+    Wood,
+}
+
+pub struct NoiseTexture {
+    noise: Perlin,
+    pattern: NoisePattern,
+    scale: f64,
+    low: TextureArc,
+    high: TextureArc,
+}
+
+impl NoiseTexture {
+    pub fn new(
+        pattern: NoisePattern,
+        scale: f64,
+        low: TextureArc,
+        high: TextureArc,
+        seed: u64,
+    ) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(seed),
+            pattern,
+            scale,
+            low,
+            high,
+        }
+    }
+
+    // Blend factor between the low and high colors, in [0, 1].
+    fn blend(&self, p: &Vec3) -> f64 {
+        const TURBULENCE_DEPTH: u32 = 7;
+        let sp = self.scale * *p;
+        let t = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.noise.noise(&sp)),
+            NoisePattern::Turbulence => self.noise.turbulence(&sp, TURBULENCE_DEPTH),
+            NoisePattern::Marble => {
+                0.5 * (1.0 + (sp.z() + 10.0 * self.noise.turbulence(&sp, TURBULENCE_DEPTH)).sin())
+            }
+            NoisePattern::Wood => {
+                // Concentric rings around the vertical axis, distorted by the noise.
+                let rings = (sp.x().powi(2) + sp.z().powi(2)).sqrt()
+                    + 2.0 * self.noise.turbulence(&(0.5 * sp), TURBULENCE_DEPTH);
+                rings.fract()
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let t = self.blend(p);
+        (1.0 - t) * self.low.value(u, v, p) + t * self.high.value(u, v, p)
+    }
+}
@@ -0,0 +1,153 @@
+// Rectangle of pixels rendered by one thread at a time, `y` is counted from the top of
+// the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Order in which the tiles are handed to the threads.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TileOrder {
+    Scanline,
+    // Outward from the center of the image, where the subject usually is.
+    #[default]
+    Spiral,
+    // Along a Hilbert curve, consecutive tiles are neighbours and share their memory.
+    Hilbert,
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        match name {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None,
+        }
+    }
+}
+
+// Splits the image in tiles of `size` pixels, smaller on the right and bottom edges,
+// covering every pixel exactly once.
+pub fn tiles(width: u32, height: u32, size: u32, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+    let tile = |(column, row): (u32, u32)| {
+        let x = column * size;
+        let y = row * size;
+        Tile {
+            x,
+            y,
+            width: size.min(width - x),
+            height: size.min(height - y),
+        }
+    };
+
+    let mut cells: Vec<(u32, u32)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center_x = (columns as f64 - 1.0) / 2.0;
+            let center_y = (rows as f64 - 1.0) / 2.0;
+            // Rings of tiles around the center, each one walked around by angle.
+            let key = |&(column, row): &(u32, u32)| {
+                let dx = column as f64 - center_x;
+                let dy = row as f64 - center_y;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| {
+                let (a, b) = (key(a), key(b));
+                a.partial_cmp(&b).expect("Tile keys are never NaN")
+            });
+        }
+        TileOrder::Hilbert => {
+            let side = columns.max(rows).next_power_of_two();
+            cells = (0..side * side)
+                .map(|d| hilbert_cell(side, d))
+                .filter(|&(column, row)| column < columns && row < rows)
+                .collect();
+        }
+    }
+    cells.into_iter().map(tile).collect()
+}
+
+// Cell at the distance `d` along the Hilbert curve filling a square of `side` cells,
+// `side` being a power of two.
+fn hilbert_cell(side: u32, d: u32) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    fn assert_covers_once(width: u32, height: u32, size: u32, order: TileOrder) {
+        let mut counts = vec![0; (width * height) as usize];
+        for tile in tiles(width, height, size, order) {
+            assert!(tile.width > 0 && tile.height > 0, "{:?}", tile);
+            assert!(tile.x + tile.width <= width && tile.y + tile.height <= height);
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    counts[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(
+            counts.iter().all(|&count| count == 1),
+            "{}x{} with size {} in {:?} order",
+            width,
+            height,
+            size,
+            order
+        );
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for &order in &ORDERS {
+            assert_covers_once(37, 23, 8, order);
+            assert_covers_once(23, 37, 8, order);
+            assert_covers_once(32, 32, 8, order);
+            assert_covers_once(37, 23, 64, order);
+            assert_covers_once(1, 1, 32, order);
+            assert_covers_once(5, 3, 1, order);
+        }
+    }
+
+    #[test]
+    fn hilbert_keeps_every_cell() {
+        let sizes: [(u32, u32, u32); 4] = [(37, 23, 8), (23, 37, 8), (100, 10, 7), (37, 23, 64)];
+        for &(width, height, size) in &sizes {
+            let columns = width.div_ceil(size);
+            let rows = height.div_ceil(size);
+            let tiles = tiles(width, height, size, TileOrder::Hilbert);
+            assert_eq!(tiles.len() as u32, columns * rows);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::vec3::Vec3;
+
+// Operators compressing the linear radiance to the [0, 1] range of integer images.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMapper {
+    #[default]
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMapper {
+    pub fn from_name(name: &str) -> Option<ToneMapper> {
+        match name {
+            "clamp" => Some(ToneMapper::Clamp),
+            "reinhard" => Some(ToneMapper::Reinhard),
+            "aces" => Some(ToneMapper::Aces),
+            _ => None,
+        }
+    }
+
+    fn map_channel(self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        let mapped = match self {
+            ToneMapper::Clamp => x,
+            ToneMapper::Reinhard => x / (1.0 + x),
+            // Curve fitted to the ACES filmic reference transform by Krzysztof Narkowicz.
+            ToneMapper::Aces => x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+        mapped.min(1.0)
+    }
+
+    // Scales the linear radiance by 2^`exposure`, maps it to [0, 1] and encodes it with
+    // the sRGB transfer function.
+    pub fn apply(self, color: &Vec3, exposure: f64) -> Vec3 {
+        let scale = 2f64.powf(exposure);
+        let encode = |c: f64| linear_to_srgb(self.map_channel(c * scale));
+        Vec3::new(encode(color.r()), encode(color.g()), encode(color.b()))
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
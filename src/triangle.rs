@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::aabb::Aabb;
+use crate::hitable::{face_normal, HitRecord, Hitable, HitableArc, Ids};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+struct Barycentric {
+    t: f64,
+    b1: f64,
+    b2: f64,
+}
+
+// Möller–Trumbore ray/triangle intersection, returns the distance along the ray
+// and the barycentric coordinates of the hit relative to v1 and v2.
+fn intersect(
+    ray: &Ray,
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    t_min: f64,
+    t_max: f64,
+) -> Option<Barycentric> {
+    const EPSILON: f64 = 1e-12;
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let p = ray.direction.cross(&e2);
+    let det = e1.dot(&p);
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = ray.origin - v0;
+    let b1 = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let b2 = ray.direction.dot(&q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = e2.dot(&q) * inv_det;
+    if t_min < t && t < t_max {
+        Some(Barycentric { t, b1, b2 })
+    } else {
+        None
+    }
+}
+
+// Uniformly distributed point on the triangle.
+fn sample_point(v0: Vec3, v1: Vec3, v2: Vec3) -> Vec3 {
+    let mut random = rand::thread_rng();
+    let su = random.gen::<f64>().sqrt();
+    let b1 = random.gen::<f64>() * su;
+    (1.0 - su) * v0 + b1 * v1 + (su - b1) * v2
+}
+
+// Solid angle density of picking `direction` when sampling a point on the triangle.
+fn direction_pdf(v0: Vec3, v1: Vec3, v2: Vec3, origin: &Vec3, direction: &Vec3) -> f64 {
+    let ray = Ray::new(*origin, *direction);
+    match intersect(&ray, v0, v1, v2, 0.0, f64::MAX) {
+        Some(bary) => {
+            let n = (v1 - v0).cross(&(v2 - v0));
+            let area = 0.5 * n.length();
+            let distance_squared = bary.t.powi(2) * direction.squared_length();
+            let cosine = n.dot(direction).abs() / (n.length() * direction.length());
+            if cosine <= 0.0 || area <= 0.0 {
+                return 0.0;
+            }
+            distance_squared / (cosine * area)
+        }
+        None => 0.0,
+    }
+}
+
+fn bounds(v0: Vec3, v1: Vec3, v2: Vec3) -> Aabb {
+    Aabb::new(v0.min(&v1).min(&v2), v0.max(&v1).max(&v2)).padded()
+}
+
+pub struct Triangle<T: Material> {
+    vertices: [Vec3; 3],
+    normal: Vec3,
+    material: T,
+    ids: Ids,
+}
+
+impl<T: Material> Triangle<T> {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: T) -> Triangle<T> {
+        Triangle {
+            vertices: [v0, v1, v2],
+            normal: (v1 - v0).cross(&(v2 - v0)).normalized(),
+            material,
+            ids: Ids::default(),
+        }
+    }
+
+    pub fn with_ids(mut self, ids: Ids) -> Triangle<T> {
+        self.ids = ids;
+        self
+    }
+}
+
+impl<T: Material> Hitable for Triangle<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let [v0, v1, v2] = self.vertices;
+        let bary = intersect(ray, v0, v1, v2, t_min, t_max)?;
+        let (normal, front_face) = face_normal(ray, self.normal);
+        Some(HitRecord {
+            t: bary.t,
+            p: ray.point(bary.t),
+            normal,
+            front_face,
+            u: bary.b1,
+            v: bary.b2,
+            material: &self.material,
+            ids: self.ids,
+            hitable: self,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [v0, v1, v2] = self.vertices;
+        Some(bounds(v0, v1, v2))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let [v0, v1, v2] = self.vertices;
+        Some(sample_point(v0, v1, v2) - *origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let [v0, v1, v2] = self.vertices;
+        direction_pdf(v0, v1, v2, origin, direction)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+pub struct Mesh<T: Material> {
+    vertices: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    material: T,
+    ids: Ids,
+}
+
+impl<T: Material> Mesh<T> {
+    pub fn new(
+        vertices: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: T,
+    ) -> Mesh<T> {
+        Mesh {
+            vertices,
+            normals,
+            uvs,
+            faces,
+            material,
+            ids: Ids::default(),
+        }
+    }
+
+    pub fn material(&self) -> &T {
+        &self.material
+    }
+
+    pub fn with_ids(mut self, ids: Ids) -> Mesh<T> {
+        self.ids = ids;
+        self
+    }
+}
+
+impl<T: Material + Send + Sync + 'static> Mesh<T> {
+    // Splits the mesh into one hitable per face so that they can be stored in a Bvh,
+    // the buffers stay shared between all the triangles.
+    pub fn triangles(self: Arc<Self>) -> Vec<HitableArc> {
+        (0..self.faces.len())
+            .map(|index| {
+                Arc::new(MeshTriangle {
+                    mesh: self.clone(),
+                    index,
+                }) as HitableArc
+            })
+            .collect()
+    }
+}
+
+pub struct MeshTriangle<T: Material> {
+    mesh: Arc<Mesh<T>>,
+    index: usize,
+}
+
+impl<T: Material> MeshTriangle<T> {
+    fn positions(&self) -> (Vec3, Vec3, Vec3) {
+        let [i0, i1, i2] = self.mesh.faces[self.index].vertices;
+        let vertices = &self.mesh.vertices;
+        (vertices[i0], vertices[i1], vertices[i2])
+    }
+}
+
+impl<T: Material> Hitable for MeshTriangle<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (v0, v1, v2) = self.positions();
+        let bary = intersect(ray, v0, v1, v2, t_min, t_max)?;
+        let face = &self.mesh.faces[self.index];
+        let b0 = 1.0 - bary.b1 - bary.b2;
+
+        // The side is given by the winding of the face, the interpolated normal is turned
+        // the same way.
+        let (geometric_normal, front_face) =
+            face_normal(ray, (v1 - v0).cross(&(v2 - v0)).normalized());
+        let normal = match face.normals {
+            Some([n0, n1, n2]) => {
+                let normals = &self.mesh.normals;
+                let normal =
+                    (b0 * normals[n0] + bary.b1 * normals[n1] + bary.b2 * normals[n2]).normalized();
+                if front_face {
+                    normal
+                } else {
+                    -normal
+                }
+            }
+            None => geometric_normal,
+        };
+        let (u, v) = match face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                (
+                    b0 * uvs[t0].0 + bary.b1 * uvs[t1].0 + bary.b2 * uvs[t2].0,
+                    b0 * uvs[t0].1 + bary.b1 * uvs[t1].1 + bary.b2 * uvs[t2].1,
+                )
+            }
+            None => (bary.b1, bary.b2),
+        };
+
+        Some(HitRecord {
+            t: bary.t,
+            p: ray.point(bary.t),
+            normal,
+            front_face,
+            u,
+            v,
+            material: &self.mesh.material,
+            ids: self.mesh.ids,
+            hitable: self,
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (v0, v1, v2) = self.positions();
+        Some(bounds(v0, v1, v2))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+
+    fn sample_direction(&self, origin: &Vec3) -> Option<Vec3> {
+        let (v0, v1, v2) = self.positions();
+        Some(sample_point(v0, v1, v2) - *origin)
+    }
+
+    fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let (v0, v1, v2) = self.positions();
+        direction_pdf(v0, v1, v2, origin, direction)
+    }
+}
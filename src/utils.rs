@@ -21,3 +21,20 @@ pub fn random_in_unit_sphere() -> Vec3 {
         }
     }
 }
+
+pub fn random_unit_vector() -> Vec3 {
+    random_in_unit_sphere().normalized()
+}
+
+// Builds an orthonormal basis around `w`, returned as (u, v, w).
+pub fn onb(w: &Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = w.normalized();
+    let a = if w.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let v = w.cross(&a).normalized();
+    let u = w.cross(&v);
+    (u, v, w)
+}
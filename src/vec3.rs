@@ -51,6 +51,31 @@ impl Vec3 {
         self.e[0] * v.e[0] + self.e[1] * v.e[1] + self.e[2] * v.e[2]
     }
 
+    pub fn min(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.e[0].min(v.e[0]),
+            self.e[1].min(v.e[1]),
+            self.e[2].min(v.e[2]),
+        )
+    }
+
+    pub fn max(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.e[0].max(v.e[0]),
+            self.e[1].max(v.e[1]),
+            self.e[2].max(v.e[2]),
+        )
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.e[0].max(self.e[1]).max(self.e[2])
+    }
+
+    // Relative luminance of a linear Rec. 709 color.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.e[0] + 0.7152 * self.e[1] + 0.0722 * self.e[2]
+    }
+
     pub fn cross(&self, v: &Vec3) -> Vec3 {
         Vec3 {
             e: [
@@ -62,6 +87,13 @@ impl Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
 impl ops::Neg for Vec3 {
     type Output = Vec3;
     fn neg(self) -> Vec3 {